@@ -0,0 +1,270 @@
+//! Photos stored in ZIP archives
+
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
+
+use bytes::Bytes;
+use zip::ZipArchive;
+
+use crate::{error::ErrorToString, source::PhotoSource};
+
+/// Serves folders of another source that are ZIP archives, e.g. `2024.zip`. Listing a folder
+/// downloads the whole archive to a file in the cache folder, and photos are read from that file
+/// until the folder is listed again. Archives that can't be listed anymore are removed from the
+/// cache, and so is the cache folder when the source is dropped
+pub struct ArchiveSource<S> {
+    source: S,
+    /// Folder the downloaded archives are stored in
+    cache_dir: PathBuf,
+    /// Downloaded archives by folder path
+    archives: HashMap<String, CachedArchive>,
+    /// Numbers the cached archive files
+    next_file_id: u64,
+}
+
+/// Downloaded archive, open for reading entries
+struct CachedArchive {
+    archive: ZipArchive<File>,
+    path: PathBuf,
+}
+
+impl<S: PhotoSource> ArchiveSource<S> {
+    /// Caches archives in a folder of the system temporary folder (see [env::temp_dir])
+    pub fn new(source: S) -> ArchiveSource<S> {
+        ArchiveSource {
+            source,
+            cache_dir: env::temp_dir().join(format!("syno-photo-frame-{}", process::id())),
+            archives: HashMap::new(),
+            next_file_id: 0,
+        }
+    }
+
+    /// Caches archives in `cache_dir` instead, which is created when needed and removed when the
+    /// source is dropped
+    pub fn with_cache_dir(mut self, cache_dir: PathBuf) -> Self {
+        self.cache_dir = cache_dir;
+        self
+    }
+
+    /// Downloads the archive of a folder into a new cache file and opens it
+    fn download(&mut self, folder: &str) -> Result<CachedArchive, String> {
+        let bytes = self.source.retrieve(folder)?;
+        fs::create_dir_all(&self.cache_dir).map_err_to_string()?;
+        let path = self.cache_dir.join(format!("{}.zip", self.next_file_id));
+        self.next_file_id += 1;
+        fs::write(&path, bytes).map_err_to_string()?;
+        let archive = File::open(&path)
+            .map_err_to_string()
+            .and_then(|file| ZipArchive::new(file).map_err_to_string());
+        match archive {
+            Ok(archive) => Ok(CachedArchive { archive, path }),
+            Err(error) => {
+                remove_file(&path);
+                Err(error)
+            }
+        }
+    }
+
+    /// Drops the cached archive of a folder, if any
+    fn evict(&mut self, folder: &str) {
+        if let Some(cached) = self.archives.remove(folder) {
+            /* Closes the file first, which can't be removed while open on Windows */
+            drop(cached.archive);
+            remove_file(&cached.path);
+        }
+    }
+}
+
+impl<S: PhotoSource> PhotoSource for ArchiveSource<S> {
+    fn list(&mut self, folder: &str) -> Result<Vec<String>, String> {
+        self.evict(folder);
+        let mut cached = self
+            .download(folder)
+            .map_err(|error| format!("{folder}: {error}"))?;
+        let file_names = file_names(&mut cached.archive);
+        self.archives.insert(folder.to_string(), cached);
+        Ok(file_names)
+    }
+
+    fn retrieve(&mut self, path: &str) -> Result<Bytes, String> {
+        let (archive, entry_name) = self
+            .archives
+            .iter_mut()
+            .find_map(|(folder, cached)| {
+                let entry_name = path.strip_prefix(folder.as_str())?.strip_prefix('/')?;
+                Some((&mut cached.archive, entry_name))
+            })
+            .ok_or_else(|| format!("{path} is not in a listed archive"))?;
+        /* Not preallocated from the entry size, which the archive may misstate */
+        let mut bytes = vec![];
+        archive
+            .by_name(entry_name)
+            .and_then(|mut entry| Ok(entry.read_to_end(&mut bytes)?))
+            .map_err(|error| format!("{path}: {error}"))?;
+        Ok(Bytes::from(bytes))
+    }
+
+    /// Entries of listed archives are files. Folders can't be treated as single files, as they
+    /// are archives
+    fn is_file(&mut self, _path: &str) -> bool {
+        false
+    }
+
+    fn is_reachable(&self, timeout: Duration) -> bool {
+        self.source.is_reachable(timeout)
+    }
+}
+
+impl<S> Drop for ArchiveSource<S> {
+    fn drop(&mut self) {
+        self.archives.clear();
+        if let Err(error) = fs::remove_dir_all(&self.cache_dir) {
+            log::debug!("{}: {error}", self.cache_dir.to_string_lossy());
+        }
+    }
+}
+
+/// Names of file entries in central directory order. Folders and encrypted files are left out
+fn file_names(archive: &mut ZipArchive<File>) -> Vec<String> {
+    (0..archive.len())
+        .filter_map(|index| {
+            let entry = archive.by_index_raw(index).ok()?;
+            (!entry.is_dir() && !entry.encrypted()).then(|| entry.name().to_string())
+        })
+        .collect()
+}
+
+fn remove_file(path: &Path) {
+    if let Err(error) = fs::remove_file(path) {
+        log::debug!("{}: {error}", path.to_string_lossy());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    use super::*;
+    use crate::source::MockPhotoSource;
+
+    #[test]
+    fn archive_source_lists_and_extracts_stored_and_deflated_entries() {
+        let zip = zip(&[
+            ("photos/a.jpg", b"stored photo", CompressionMethod::Stored),
+            (
+                "b.jpg",
+                b"deflated photo deflated photo",
+                CompressionMethod::Deflated,
+            ),
+        ]);
+        let mut source_stub = MockPhotoSource::new();
+        source_stub
+            .expect_retrieve()
+            .withf(|path| path == "albums/2024.zip")
+            .returning(move |_| Ok(zip.clone()));
+        let mut archive_source = ArchiveSource::new(source_stub).with_cache_dir(cache_dir("list"));
+
+        let file_names = archive_source.list("albums/2024.zip").unwrap();
+
+        assert_eq!(file_names, ["photos/a.jpg", "b.jpg"]);
+        assert_eq!(
+            archive_source.retrieve("albums/2024.zip/photos/a.jpg"),
+            Ok(Bytes::from_static(b"stored photo"))
+        );
+        assert_eq!(
+            archive_source.retrieve("albums/2024.zip/b.jpg"),
+            Ok(Bytes::from_static(b"deflated photo deflated photo"))
+        );
+        assert!(archive_source
+            .retrieve("albums/2024.zip/.frameignore")
+            .is_err());
+    }
+
+    #[test]
+    fn zip64_entries_are_extracted() {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        writer
+            .start_file("a.jpg", SimpleFileOptions::default().large_file(true))
+            .unwrap();
+        writer.write_all(b"photo").unwrap();
+        let zip = Bytes::from(writer.finish().unwrap().into_inner());
+        let mut source_stub = MockPhotoSource::new();
+        source_stub
+            .expect_retrieve()
+            .returning(move |_| Ok(zip.clone()));
+        let mut archive_source = ArchiveSource::new(source_stub).with_cache_dir(cache_dir("zip64"));
+
+        archive_source.list("2024.zip").unwrap();
+
+        assert_eq!(
+            archive_source.retrieve("2024.zip/a.jpg"),
+            Ok(Bytes::from_static(b"photo"))
+        );
+    }
+
+    #[test]
+    fn when_archive_can_not_be_listed_anymore_then_it_is_evicted() {
+        let zip = zip(&[("a.jpg", b"photo", CompressionMethod::Stored)]);
+        let mut source_stub = MockPhotoSource::new();
+        source_stub
+            .expect_retrieve()
+            .times(1)
+            .returning(move |_| Ok(zip.clone()));
+        source_stub
+            .expect_retrieve()
+            .returning(|_| Err("550 File not found".to_string()));
+        let cache_dir = cache_dir("evict");
+        let mut archive_source = ArchiveSource::new(source_stub).with_cache_dir(cache_dir.clone());
+        archive_source.list("2024.zip").unwrap();
+
+        let result = archive_source.list("2024.zip");
+
+        assert!(result.is_err());
+        assert!(archive_source.retrieve("2024.zip/a.jpg").is_err());
+        assert_eq!(fs::read_dir(&cache_dir).unwrap().count(), 0);
+        drop(archive_source);
+        assert!(!cache_dir.exists());
+    }
+
+    #[test]
+    fn when_file_is_not_a_zip_archive_then_listing_fails() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub
+            .expect_retrieve()
+            .returning(|_| Ok(Bytes::from_static(b"not an archive")));
+        let mut archive_source =
+            ArchiveSource::new(source_stub).with_cache_dir(cache_dir("invalid"));
+
+        let result = archive_source.list("photo.jpg");
+
+        assert!(result.is_err());
+    }
+
+    /// Cache folder unique to the test
+    fn cache_dir(test: &str) -> PathBuf {
+        env::temp_dir().join(format!("archive_test_{test}_{}", process::id()))
+    }
+
+    /// Writes a ZIP archive with the given entries (name, contents, compression method)
+    fn zip(entries: &[(&str, &[u8], CompressionMethod)]) -> Bytes {
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        writer
+            .add_directory("photos/", SimpleFileOptions::default())
+            .unwrap();
+        for &(name, contents, method) in entries {
+            let options = SimpleFileOptions::default().compression_method(method);
+            writer.start_file(name, options).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        Bytes::from(writer.finish().unwrap().into_inner())
+    }
+}
@@ -1,8 +1,12 @@
+//! Built-in screens displayed in place of photos. Public so that tools can preview them at a
+//! given screen size without initializing SDL
+
 use crate::{
     cli::Rotation,
     img::{self, DynamicImage, Framed},
 };
 
+/// Startup screen displayed until the first photo is ready, unless replaced with --splash
 pub fn welcome_screen(screen_size: (u32, u32), rotation: Rotation) -> Result<DynamicImage, String> {
     #[cfg(not(target_os = "windows"))]
     const LOADING: &[u8] = include_bytes!("../assets/Loading.jpeg");
@@ -11,6 +15,7 @@ pub fn welcome_screen(screen_size: (u32, u32), rotation: Rotation) -> Result<Dyn
     load_and_resize(LOADING, screen_size, rotation)
 }
 
+/// Screen displayed when fetching or processing a photo fails
 pub fn error_screen(screen_size: (u32, u32), rotation: Rotation) -> Result<DynamicImage, String> {
     #[cfg(not(target_os = "windows"))]
     const ERROR_BYTES: &[u8] = include_bytes!("../assets/Error.jpeg");
@@ -26,3 +31,19 @@ fn load_and_resize(
 ) -> Result<DynamicImage, String> {
     Ok(img::load_from_memory(bytes)?.fit_to_screen(screen_size, rotation))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn screens_fill_the_screen() {
+        let screen_size = (192, 108);
+
+        let welcome = welcome_screen(screen_size, Rotation::D90).unwrap();
+        let error = error_screen(screen_size, Rotation::D0).unwrap();
+
+        assert_eq!((welcome.width(), welcome.height()), screen_size);
+        assert_eq!((error.width(), error.height()), screen_size);
+    }
+}
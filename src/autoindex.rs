@@ -0,0 +1,267 @@
+//! Photos served by an HTTP server with directory index listings
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use serde::Deserialize;
+
+use crate::{
+    error::ErrorToString,
+    http::{Client, Response, Url},
+    source::{self, PhotoSource},
+};
+
+/// Serves photos from an HTTP(S) server generating directory listings for folders, e.g. nginx
+/// `autoindex`, Apache `mod_autoindex` or `python -m http.server`. Listings are either HTML pages
+/// linking to the folder entries or nginx JSON listings (`autoindex_format json`)
+pub struct AutoindexSource<C> {
+    client: C,
+    /// Album folder URL, including the credentials, if any, sent as basic authentication
+    server: Url,
+}
+
+/// Entry of an nginx JSON listing
+#[derive(Debug, Deserialize)]
+struct JsonEntry {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+impl<C: Client> AutoindexSource<C> {
+    pub fn new(
+        client: C,
+        server: &Url,
+        user: &Option<String>,
+        password: &Option<String>,
+    ) -> Result<AutoindexSource<C>, String> {
+        let mut server = server.clone();
+        if let Some(user) = user {
+            server
+                .set_username(user)
+                .map_err(|_| "server URL does not accept a user".to_string())?;
+        }
+        if password.is_some() {
+            server
+                .set_password(password.as_deref())
+                .map_err(|_| "server URL does not accept a password".to_string())?;
+        }
+        Ok(AutoindexSource { client, server })
+    }
+
+    /// Resolves a path relative to the album folder. Folder URLs end with a slash, which
+    /// servers otherwise answer with a redirect
+    fn url(&self, path: &str, is_folder: bool) -> Result<Url, String> {
+        let mut url = self.server.clone();
+        {
+            let mut segments = url
+                .path_segments_mut()
+                .map_err(|_| "server URL can't have a path".to_string())?;
+            segments
+                .pop_if_empty()
+                .extend(path.split('/').filter(|segment| !segment.is_empty()));
+            if is_folder {
+                segments.push("");
+            }
+        }
+        Ok(url)
+    }
+
+    fn get(&self, url: &Url, path: &str) -> Result<C::Response, String> {
+        let response = self.client.get(url.as_str(), &[])?;
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            status => Err(format!("{path}: {status}")),
+        }
+    }
+}
+
+impl<C: Client> PhotoSource for AutoindexSource<C> {
+    fn list(&mut self, folder: &str) -> Result<Vec<String>, String> {
+        let url = self.url(folder, true)?;
+        let listing = self.get(&url, folder)?.text()?;
+        if listing.trim_start().starts_with('[') {
+            parse_json_listing(&listing).map_err(|error| format!("{folder}: {error}"))
+        } else {
+            Ok(parse_html_listing(&listing, &url))
+        }
+    }
+
+    fn retrieve(&mut self, path: &str) -> Result<Bytes, String> {
+        let url = self.url(path, false)?;
+        self.get(&url, path)?.bytes()
+    }
+
+    /// Listings tell files from folders, so a path is a file when its folder lists it
+    fn is_file(&mut self, path: &str) -> bool {
+        let (folder, file_name) = path.rsplit_once('/').unwrap_or(("", path));
+        self.list(folder)
+            .is_ok_and(|file_names| file_names.iter().any(|name| name == file_name))
+    }
+
+    fn is_reachable(&self, timeout: Duration) -> bool {
+        let default_port = self.server.port_or_known_default().unwrap_or(80);
+        source::is_reachable(&self.server, default_port, timeout)
+    }
+}
+
+fn parse_json_listing(listing: &str) -> Result<Vec<String>, String> {
+    let entries: Vec<JsonEntry> = serde_json::from_str(listing).map_err_to_string()?;
+    Ok(entries
+        .into_iter()
+        .filter(|entry| entry.kind == "file")
+        .map(|entry| entry.name)
+        .collect())
+}
+
+/// Lists file names linked from an HTML listing of `folder_url`. Links leaving the folder (e.g.
+/// to the parent folder), to subfolders, or with a query (e.g. Apache's sort order links) are
+/// skipped
+fn parse_html_listing(listing: &str, folder_url: &Url) -> Vec<String> {
+    let mut file_names: Vec<String> = vec![];
+    for href in listing.split("href=\"").skip(1) {
+        let Some((href, _)) = href.split_once('"') else {
+            continue;
+        };
+        let Ok(url) = folder_url.join(&href.replace("&amp;", "&")) else {
+            continue;
+        };
+        if url.origin() != folder_url.origin() || url.query().is_some() {
+            continue;
+        }
+        let Some(name) = url.path().strip_prefix(folder_url.path()) else {
+            continue;
+        };
+        if name.is_empty() || name.contains('/') {
+            continue;
+        }
+        let name = percent_decode(name);
+        if !file_names.contains(&name) {
+            file_names.push(name);
+        }
+    }
+    file_names
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::http::{MockResponse, StatusCode};
+
+    /// Answers GET requests with the body registered for the URL, or 404 Not Found
+    struct ClientStub {
+        bodies: HashMap<&'static str, &'static str>,
+    }
+
+    impl Client for ClientStub {
+        type Response = MockResponse;
+
+        fn post(
+            &self,
+            url: &str,
+            _form: &[(&str, &str)],
+            _header: Option<(&str, &str)>,
+        ) -> Result<MockResponse, String> {
+            panic!("unexpected POST {url}, listings and photos are only fetched with GET")
+        }
+
+        fn get(&self, url: &str, _query: &[(&str, &str)]) -> Result<MockResponse, String> {
+            let body = self.bodies.get(url).copied();
+            let mut response = MockResponse::new();
+            response.expect_status().return_const(match body {
+                Some(_) => StatusCode::OK,
+                None => StatusCode::NOT_FOUND,
+            });
+            response
+                .expect_text()
+                .returning(move || Ok(body.unwrap_or_default().to_string()));
+            response
+                .expect_bytes()
+                .returning(move || Ok(Bytes::from(body.unwrap_or_default())));
+            Ok(response)
+        }
+    }
+
+    fn source(bodies: &[(&'static str, &'static str)]) -> AutoindexSource<ClientStub> {
+        let client = ClientStub {
+            bodies: bodies.iter().copied().collect(),
+        };
+        let server = "http://nas.lan/photos".parse().unwrap();
+        AutoindexSource::new(client, &server, &None, &None).unwrap()
+    }
+
+    #[test]
+    fn html_listing_lists_files_in_folder_only() {
+        let mut source = source(&[(
+            "http://nas.lan/photos/2024/",
+            r#"<a href="?C=N;O=D">Name</a>
+            <a href="/photos/">Parent Directory</a>
+            <a href="../">../</a>
+            <a href="summer/">summer/</a>
+            <a href="a.jpg">a.jpg</a>
+            <a href="./b%20%26%20c.jpg">b &amp; c.jpg</a>
+            <a href="/photos/2024/d.png">d.png</a>
+            <a href="http://other.lan/e.jpg">e.jpg</a>"#,
+        )]);
+
+        let file_names = source.list("2024").unwrap();
+
+        assert_eq!(file_names, ["a.jpg", "b & c.jpg", "d.png"]);
+    }
+
+    #[test]
+    fn json_listing_lists_files_only() {
+        let mut source = source(&[(
+            "http://nas.lan/photos/",
+            r#"[{"name":"2024","type":"directory"},{"name":"a.jpg","type":"file","size":1}]"#,
+        )]);
+
+        let file_names = source.list("").unwrap();
+
+        assert_eq!(file_names, ["a.jpg"]);
+    }
+
+    #[test]
+    fn photos_are_retrieved_by_escaped_url() {
+        let mut source = source(&[
+            ("http://nas.lan/photos/2024/", r#"<a href="b%20c.jpg">"#),
+            ("http://nas.lan/photos/2024/b%20c.jpg", "photo"),
+        ]);
+
+        assert_eq!(
+            source.retrieve("2024/b c.jpg"),
+            Ok(Bytes::from_static(b"photo"))
+        );
+        assert!(source.is_file("2024/b c.jpg"));
+        assert!(!source.is_file("2024"));
+        assert_eq!(
+            source.retrieve("missing.jpg"),
+            Err("missing.jpg: 404 Not Found".to_string())
+        );
+    }
+}
@@ -0,0 +1,587 @@
+//! FTP request-response handling
+
+use std::{
+    io::{self, Read, Write},
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, TcpStream},
+    thread,
+    time::Duration,
+};
+
+use bytes::Bytes;
+use ftp::{types::FileType, FtpStream};
+
+use crate::{
+    error::ErrorToString,
+    http::Url,
+    source::{self, PhotoSource},
+};
+
+/// Used when the server URL does not specify a port
+const FTP_PORT: u16 = 21;
+
+/* Reply codes not covered by ftp::status */
+const DATA_CONNECTION_ALREADY_OPEN: u32 = 125;
+const ABOUT_TO_OPEN_DATA_CONNECTION: u32 = 150;
+const COMMAND_OK: u32 = 200;
+const SYSTEM_STATUS: u32 = 211;
+const FILE_STATUS: u32 = 213;
+const CLOSING_DATA_CONNECTION: u32 = 226;
+const ENTERING_PASSIVE_MODE: u32 = 227;
+const ENTERING_EXTENDED_PASSIVE_MODE: u32 = 229;
+const REQUESTED_FILE_ACTION_OK: u32 = 250;
+
+/// Number of times a request is repeated after a transient error, waiting one more
+/// [TRANSIENT_ERROR_RETRY_DELAY] before each retry
+const TRANSIENT_ERROR_RETRIES: u32 = 3;
+const TRANSIENT_ERROR_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Starts error messages of transfers that failed or ended early because the data connection
+/// was reset. These are retried like transient errors
+const INCOMPLETE_TRANSFER: &str = "Incomplete transfer";
+
+/// Size of the buffer data connections are read with, unless set with
+/// [Client::with_transfer_buffer]
+const DEFAULT_TRANSFER_BUFFER_SIZE: usize = 64 * 1024;
+
+/// FTP server connection details. Each request is sent over a new [Session]
+pub struct Client {
+    server: Url,
+    user: String,
+    password: String,
+    ignore_pasv_addr: bool,
+    transfer_buffer_size: usize,
+}
+
+impl Client {
+    /// Anonymous login is used when `user` is not set
+    pub fn new(server: &Url, user: &Option<String>, password: &Option<String>) -> Client {
+        Client {
+            server: server.clone(),
+            user: user.as_deref().unwrap_or("anonymous").to_string(),
+            password: password.as_deref().unwrap_or_default().to_string(),
+            ignore_pasv_addr: true,
+            transfer_buffer_size: DEFAULT_TRANSFER_BUFFER_SIZE,
+        }
+    }
+
+    pub fn with_pasv_ignore_addr(mut self, ignore: bool) -> Self {
+        self.ignore_pasv_addr = ignore;
+        self
+    }
+
+    /// Sets the size in bytes of the buffer data connections are read with. Larger buffers speed
+    /// up downloads of large files over fast links
+    pub fn with_transfer_buffer(mut self, size: usize) -> Self {
+        self.transfer_buffer_size = size;
+        self
+    }
+
+    /// Sends a request over a new session. Requests failing with a transient error (e.g. when
+    /// the server is busy) or an incomplete transfer are retried after a short wait, other errors
+    /// are returned right away
+    fn with_session<T>(
+        &self,
+        request: impl Fn(&mut Session) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut retries = 0;
+        loop {
+            let result = Session::connect(
+                &self.server,
+                &self.user,
+                &self.password,
+                self.ignore_pasv_addr,
+            )
+            .and_then(|session| {
+                let mut session = session.with_transfer_buffer(self.transfer_buffer_size);
+                let result = request(&mut session);
+                session.quit();
+                result
+            });
+            match result {
+                Err(error)
+                    if retries < TRANSIENT_ERROR_RETRIES
+                        && (is_transient_error(&error) || is_incomplete_transfer(&error)) =>
+                {
+                    retries += 1;
+                    let delay = TRANSIENT_ERROR_RETRY_DELAY * retries;
+                    log::warn!("{error}, retrying in {} seconds", delay.as_secs());
+                    thread::sleep(delay);
+                }
+                result => break result,
+            }
+        }
+    }
+}
+
+impl PhotoSource for Client {
+    fn list(&mut self, folder: &str) -> Result<Vec<String>, String> {
+        self.with_session(|session| session.list(folder))
+    }
+
+    fn retrieve(&mut self, path: &str) -> Result<Bytes, String> {
+        self.with_session(|session| session.retrieve(path))
+    }
+
+    fn is_file(&mut self, path: &str) -> bool {
+        /* SIZE fails for folders */
+        self.with_session(|session| Ok(session.size(path)))
+            .is_ok_and(|size| size.is_some())
+    }
+
+    fn is_reachable(&self, timeout: Duration) -> bool {
+        source::is_reachable(&self.server, FTP_PORT, timeout)
+    }
+}
+
+/// Logged-in FTP control connection with the album folder as working directory
+pub struct Session {
+    stream: FtpStream,
+    ignore_pasv_addr: bool,
+    transfer_buffer_size: usize,
+    /// Whether the server lists folders with MLSD (RFC 3659), which tells files from subfolders
+    supports_mlsd: bool,
+}
+
+impl Session {
+    /// Connects to the server, logs in and changes the working directory to the path of
+    /// `ftp_server`
+    pub fn connect(
+        ftp_server: &Url,
+        user: &str,
+        password: &str,
+        ignore_pasv_addr: bool,
+    ) -> Result<Session, String> {
+        let addrs = source::socket_addrs(ftp_server, FTP_PORT)?;
+        let mut stream = FtpStream::connect(&addrs[..]).map_err_to_string()?;
+        stream.login(user, password).map_err_to_string()?;
+        let mut session = Session {
+            stream,
+            ignore_pasv_addr,
+            transfer_buffer_size: DEFAULT_TRANSFER_BUFFER_SIZE,
+            supports_mlsd: false,
+        };
+        let features = session.features();
+        session.supports_mlsd = features.iter().any(|feature| is_feature(feature, "MLST"));
+        /* Before CWD, as the album path may contain non-ASCII characters too */
+        session.enable_utf8(&features);
+        session
+            .stream
+            .cwd(&source::album_path(ftp_server)?)
+            .map_err_to_string()?;
+        session
+            .stream
+            .transfer_type(FileType::Binary)
+            .map_err_to_string()?;
+        Ok(session)
+    }
+
+    /// Sets the size in bytes of the buffer data connections are read with
+    pub fn with_transfer_buffer(mut self, size: usize) -> Self {
+        self.transfer_buffer_size = size;
+        self
+    }
+
+    /// Lists file names in a folder relative to the working directory (the working directory
+    /// itself when `folder` is empty). Subfolders are left out
+    pub fn list(&mut self, folder: &str) -> Result<Vec<String>, String> {
+        if !folder.is_empty() {
+            self.stream.cwd(folder).map_err_to_string()?;
+        }
+        if self.supports_mlsd {
+            let bytes = self.transfer("MLSD")?;
+            return Ok(parse_mlsd_files(&String::from_utf8_lossy(&bytes)));
+        }
+        let bytes = self.transfer("NLST")?;
+        let names: Vec<String> = String::from_utf8_lossy(&bytes)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect();
+        /* NLST lists subfolders too. Changing into an entry only succeeds for folders */
+        let mut file_names = Vec::with_capacity(names.len());
+        for name in names {
+            if self.stream.cwd(&name).is_ok() {
+                self.stream.cwd("..").map_err_to_string()?;
+            } else {
+                file_names.push(name);
+            }
+        }
+        Ok(file_names)
+    }
+
+    /// Retrieves a file by its path relative to the working directory. Fails with an
+    /// [INCOMPLETE_TRANSFER] error when fewer bytes than the size reported by the server arrive
+    pub fn retrieve(&mut self, path: &str) -> Result<Bytes, String> {
+        let expected_size = self.size(path);
+        let data = self.transfer(&format!("RETR {path}"))?;
+        match expected_size {
+            Some(size) if data.len() as u64 != size => Err(format!(
+                "{INCOMPLETE_TRANSFER} of {path}: {} of {size} bytes received",
+                data.len()
+            )),
+            _ => Ok(Bytes::from(data)),
+        }
+    }
+
+    /// Queries the size of a file with the SIZE command (RFC 3659). None when the server does
+    /// not support it
+    fn size(&mut self, path: &str) -> Option<u64> {
+        let reply = self
+            .send(&format!("SIZE {path}"))
+            .and_then(|_| self.stream.read_response(FILE_STATUS).map_err_to_string());
+        match reply {
+            Ok(reply) => reply.1.get(4..)?.trim().parse().ok(),
+            Err(error) => {
+                log::debug!("FTP SIZE: {error}");
+                None
+            }
+        }
+    }
+
+    /// Terminates the connection to the server
+    pub fn quit(mut self) {
+        if let Err(error) = self.stream.quit() {
+            log::debug!("FTP QUIT: {error}");
+        }
+    }
+
+    /// Features the server advertises in its FEAT reply. None when it does not support FEAT
+    fn features(&mut self) -> Vec<String> {
+        match self.send("FEAT").and_then(|_| self.read_multiline_reply()) {
+            Ok(reply) => parse_feat_reply(&reply),
+            Err(error) => {
+                log::debug!("FTP FEAT: {error}");
+                vec![]
+            }
+        }
+    }
+
+    /// Switches the server to UTF-8 file names when it advertises support in its FEAT reply.
+    /// Servers without UTF-8 support are used as they are
+    fn enable_utf8(&mut self, features: &[String]) {
+        if !features.iter().any(|feature| is_feature(feature, "UTF8")) {
+            log::debug!("FTP server does not support UTF8");
+            return;
+        }
+        let opts_result = self
+            .send("OPTS UTF8 ON")
+            .and_then(|_| self.stream.read_response(COMMAND_OK).map_err_to_string());
+        if let Err(error) = opts_result {
+            log::debug!("FTP OPTS UTF8 ON: {error}");
+        }
+    }
+
+    /// Reads all lines of a reply directly from the control connection.
+    ///
+    /// [FtpStream::read_response] only returns the last line of a multiline reply. Reading
+    /// bypasses its buffer, which is empty as long as all previous replies have been read.
+    /// Reading byte by byte makes sure nothing past the reply is consumed.
+    #[allow(clippy::unbuffered_bytes)]
+    fn read_multiline_reply(&self) -> Result<Vec<String>, String> {
+        let mut lines = vec![];
+        let mut line = vec![];
+        for byte in self.stream.get_ref().bytes() {
+            match byte.map_err_to_string()? {
+                b'\n' => {
+                    let text = String::from_utf8_lossy(&line).trim_end().to_string();
+                    line.clear();
+                    let is_last = is_last_reply_line(&text);
+                    lines.push(text);
+                    if is_last {
+                        return Ok(lines);
+                    }
+                }
+                byte => line.push(byte),
+            }
+        }
+        Err("Connection closed while reading reply".to_string())
+    }
+
+    /// Sends a command that uses a passive mode data connection and reads all the data sent by
+    /// the server.
+    ///
+    /// The data connection is set up here rather than by [FtpStream], which always connects to
+    /// the address reported in the PASV reply. That address is unreachable when the server sits
+    /// behind NAT and reports its private LAN address.
+    fn transfer(&mut self, command: &str) -> Result<Vec<u8>, String> {
+        let control_addr = self.stream.get_ref().peer_addr().map_err_to_string()?;
+        let data_addr = if control_addr.is_ipv6() {
+            self.extended_passive_addr(control_addr)?
+        } else {
+            self.passive_addr(control_addr)?
+        };
+        let mut data_stream = TcpStream::connect(data_addr).map_err_to_string()?;
+
+        self.send(command)?;
+        self.stream
+            .read_response_in(&[DATA_CONNECTION_ALREADY_OPEN, ABOUT_TO_OPEN_DATA_CONNECTION])
+            .map_err_to_string()?;
+        let data = read_all(&mut data_stream, self.transfer_buffer_size)
+            .map_err(|error| format!("{INCOMPLETE_TRANSFER}: {error}"))?;
+        drop(data_stream);
+        self.stream
+            .read_response_in(&[CLOSING_DATA_CONNECTION, REQUESTED_FILE_ACTION_OK])
+            .map_err_to_string()?;
+        Ok(data)
+    }
+
+    /// Data connection address from the PASV reply, which only supports IPv4
+    fn passive_addr(&mut self, control_addr: SocketAddr) -> Result<SocketAddr, String> {
+        self.send("PASV")?;
+        let reply = self
+            .stream
+            .read_response(ENTERING_PASSIVE_MODE)
+            .map_err_to_string()?
+            .1;
+        let pasv_addr = parse_pasv_reply(&reply)?;
+        Ok(if self.ignore_pasv_addr {
+            SocketAddr::new(control_addr.ip(), pasv_addr.port())
+        } else {
+            SocketAddr::V4(pasv_addr)
+        })
+    }
+
+    /// Data connection address from the EPSV reply (RFC 2428), used over IPv6. The reply only
+    /// contains the port, the address is the one of the control connection
+    fn extended_passive_addr(&mut self, control_addr: SocketAddr) -> Result<SocketAddr, String> {
+        self.send("EPSV")?;
+        let reply = self
+            .stream
+            .read_response(ENTERING_EXTENDED_PASSIVE_MODE)
+            .map_err_to_string()?
+            .1;
+        let port = parse_epsv_reply(&reply)?;
+        Ok(SocketAddr::new(control_addr.ip(), port))
+    }
+
+    /// Writes a raw command to the control connection. The reply must be read with
+    /// [FtpStream::read_response] afterward.
+    fn send(&self, command: &str) -> Result<(), String> {
+        let mut control_stream = self.stream.get_ref();
+        control_stream
+            .write_all(format!("{command}\r\n").as_bytes())
+            .map_err_to_string()
+    }
+}
+
+/// Reads until the end of the stream, `buffer_size` bytes at a time at most
+fn read_all(reader: &mut impl Read, buffer_size: usize) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let mut buffer = vec![0; buffer_size];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok(data),
+            Ok(read) => data.extend_from_slice(&buffer[..read]),
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// The last line of a reply starts with the reply code followed by a space (or nothing), while
+/// preceding lines of a multiline reply do not
+fn is_last_reply_line(line: &str) -> bool {
+    let bytes = line.as_bytes();
+    bytes.len() >= 3
+        && bytes[..3].iter().all(u8::is_ascii_digit)
+        && bytes.get(3).map_or(true, |&separator| separator == b' ')
+}
+
+/// Returns features listed in a FEAT reply, e.g. "UTF8" or "MDTM"
+fn parse_feat_reply(reply: &[String]) -> Vec<String> {
+    match reply {
+        [first, features @ .., _last] if first.starts_with(&SYSTEM_STATUS.to_string()) => features
+            .iter()
+            .map(|feature| feature.trim().to_string())
+            .filter(|feature| !feature.is_empty())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Checks whether a FEAT reply line advertises `name`. Some features are followed by their
+/// parameters, e.g. "MLST type*;size*;"
+fn is_feature(feature: &str, name: &str) -> bool {
+    feature
+        .split_whitespace()
+        .next()
+        .is_some_and(|feature_name| feature_name.eq_ignore_ascii_case(name))
+}
+
+/// Returns names of files in an MLSD listing (RFC 3659), made of lines such as
+/// "type=file;size=1024; IMG_1.jpg". Entries of other types, e.g. folders, are left out
+fn parse_mlsd_files(listing: &str) -> Vec<String> {
+    listing
+        .lines()
+        .filter_map(|line| {
+            let (facts, name) = line.split_once(' ')?;
+            let is_file = facts.split(';').any(|fact| {
+                fact.split_once('=').is_some_and(|(key, value)| {
+                    key.eq_ignore_ascii_case("type") && value.eq_ignore_ascii_case("file")
+                })
+            });
+            (is_file && !name.is_empty()).then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// Transient negative completion replies (4xx), e.g. "421 Too many connections", indicate that the
+/// request may succeed when repeated. The reply is found in the error message of [ftp::FtpStream]
+fn is_transient_error(error: &str) -> bool {
+    error
+        .split_once("got response: ")
+        .and_then(|(_, reply)| reply.get(..3))
+        .and_then(|code| code.parse::<u32>().ok())
+        .is_some_and(|code| (400..500).contains(&code))
+}
+
+fn is_incomplete_transfer(error: &str) -> bool {
+    error.starts_with(INCOMPLETE_TRANSFER)
+}
+
+/// Parses the address from a reply such as "227 Entering Passive Mode (h1,h2,h3,h4,p1,p2)"
+fn parse_pasv_reply(reply: &str) -> Result<SocketAddrV4, String> {
+    let invalid_reply = || format!("Invalid PASV reply: {}", reply.trim_end());
+    let numbers = reply
+        .get(4..)
+        .and_then(|text| text.find(|c: char| c.is_ascii_digit()).map(|i| &text[i..]))
+        .ok_or_else(invalid_reply)?
+        .split(|c: char| !c.is_ascii_digit() && c != ',')
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::parse::<u8>)
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| invalid_reply())?;
+    match numbers[..] {
+        [h1, h2, h3, h4, p1, p2] => Ok(SocketAddrV4::new(
+            Ipv4Addr::new(h1, h2, h3, h4),
+            u16::from_be_bytes([p1, p2]),
+        )),
+        _ => Err(invalid_reply()),
+    }
+}
+
+/// Parses the port of an EPSV reply, e.g. "229 Entering Extended Passive Mode (|||50069|)"
+fn parse_epsv_reply(reply: &str) -> Result<u16, String> {
+    let invalid_reply = || format!("Invalid EPSV reply: {}", reply.trim_end());
+    let (_, args) = reply.split_once('(').ok_or_else(invalid_reply)?;
+    /* The delimiter is usually '|', but any character may be used */
+    let delimiter = args.chars().next().ok_or_else(invalid_reply)?;
+    match args.split(delimiter).collect::<Vec<_>>()[..] {
+        ["", "", "", port, ..] => port.parse().map_err(|_| invalid_reply()),
+        _ => Err(invalid_reply()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_all_reads_data_larger_than_buffer() {
+        let data: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+
+        let result = read_all(&mut &data[..], 4096).unwrap();
+
+        assert_eq!(result, data);
+    }
+
+    #[test]
+    fn parse_feat_reply_returns_listed_features() {
+        let reply = ["211-Features:", " MDTM", " UTF8", " SIZE", "211 End"].map(str::to_string);
+
+        let result = parse_feat_reply(&reply);
+
+        assert_eq!(result, ["MDTM", "UTF8", "SIZE"]);
+    }
+
+    #[test]
+    fn parse_feat_reply_returns_no_features_when_command_is_not_supported() {
+        assert!(parse_feat_reply(&["500 Unknown command".to_string()]).is_empty());
+        assert!(parse_feat_reply(&["211 No features".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn is_feature_ignores_parameters_and_case() {
+        assert!(is_feature("MLST type*;size*;modify*;", "MLST"));
+        assert!(is_feature("utf8", "UTF8"));
+        assert!(!is_feature("MLSTX", "MLST"));
+    }
+
+    #[test]
+    fn parse_mlsd_files_returns_files_only() {
+        let listing = "type=cdir;perm=el; .\r\n\
+            type=pdir;perm=el; ..\r\n\
+            type=dir;modify=20240101000000; 2024\r\n\
+            Type=File;Size=5; IMG 1.jpg\r\n\
+            size=5;type=file; IMG_2.jpg\r\n";
+
+        let result = parse_mlsd_files(listing);
+
+        assert_eq!(result, ["IMG 1.jpg", "IMG_2.jpg"]);
+    }
+
+    #[test]
+    fn is_last_reply_line_detects_end_of_multiline_reply() {
+        assert!(!is_last_reply_line("211-Features:"));
+        assert!(!is_last_reply_line(" UTF8"));
+        assert!(is_last_reply_line("211 End"));
+        assert!(is_last_reply_line("211"));
+    }
+
+    #[test]
+    fn only_4xx_replies_are_transient_errors() {
+        assert!(is_transient_error(
+            "FTP InvalidResponse: Expected code [220], got response: 421 Too many connections\r\n"
+        ));
+        assert!(is_transient_error(
+            "Expected code [125, 150], got response: 450 File busy\r\n"
+        ));
+        assert!(!is_transient_error(
+            "Expected code [125, 150], got response: 550 File not found\r\n"
+        ));
+        assert!(!is_transient_error("Connection refused (os error 111)"));
+    }
+
+    #[test]
+    fn parse_pasv_reply_returns_address_and_port() {
+        let result = parse_pasv_reply("227 Entering Passive Mode (192,168,1,20,195,149).\r\n");
+
+        assert_eq!(
+            result,
+            Ok(SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 20), 50069))
+        );
+    }
+
+    #[test]
+    fn parse_pasv_reply_accepts_reply_without_parentheses() {
+        let result = parse_pasv_reply("227 Entering Passive Mode 10,0,0,1,4,1\r\n");
+
+        assert_eq!(
+            result,
+            Ok(SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 1), 1025))
+        );
+    }
+
+    #[test]
+    fn parse_epsv_reply_returns_port() {
+        let result = parse_epsv_reply("229 Entering Extended Passive Mode (|||50069|)\r\n");
+
+        assert_eq!(result, Ok(50069));
+    }
+
+    #[test]
+    fn parse_epsv_reply_fails_on_malformed_reply() {
+        assert!(parse_epsv_reply("229 Entering Extended Passive Mode (|||port|)\r\n").is_err());
+        assert!(parse_epsv_reply("229 Entering Extended Passive Mode\r\n").is_err());
+        assert!(parse_epsv_reply("229 (||50069|)").is_err());
+    }
+
+    #[test]
+    fn parse_pasv_reply_fails_on_malformed_reply() {
+        assert!(parse_pasv_reply("227 Entering Passive Mode (10,0,0,1,4)\r\n").is_err());
+        assert!(parse_pasv_reply("227 Entering Passive Mode\r\n").is_err());
+        assert!(parse_pasv_reply("227").is_err());
+    }
+}
@@ -0,0 +1,146 @@
+//! Previously displayed photos
+
+use std::{collections::VecDeque, mem};
+
+use crate::img::DynamicImage;
+
+/// Framed image with the path of the album photo it shows (the first one of a grid), None for
+/// title cards and error screens
+pub type Slide = (DynamicImage, Option<String>);
+
+/// Keeps up to `capacity` framed images to go back to without fetching them again
+#[derive(Debug)]
+pub struct PhotoHistory {
+    /// Images displayed before the current one, the most recent last
+    previous: VecDeque<Slide>,
+    /// Images we went back from, the most recent last. Displayed again before any newly fetched
+    /// photo, so that going back and forth does not skip photos
+    following: VecDeque<Slide>,
+    capacity: usize,
+}
+
+impl PhotoHistory {
+    pub fn new(capacity: usize) -> PhotoHistory {
+        PhotoHistory {
+            previous: VecDeque::with_capacity(capacity),
+            following: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Remembers an image replaced on screen by a newly fetched photo, dropping the oldest one
+    /// when the history is full
+    pub fn push(&mut self, slide: Slide) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.previous.len() == self.capacity {
+            self.previous.pop_front();
+        }
+        self.previous.push_back(slide);
+    }
+
+    /// Replaces `current` with the previous image. Returns false when there is none
+    pub fn go_back(&mut self, current: &mut Slide) -> bool {
+        match self.previous.pop_back() {
+            Some(slide) => {
+                self.following.push_back(mem::replace(current, slide));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces `current` with the image we went back from. Returns false when there is none, in
+    /// which case a newly fetched photo comes next
+    pub fn go_forward(&mut self, current: &mut Slide) -> bool {
+        match self.following.pop_back() {
+            Some(slide) => {
+                self.previous.push_back(mem::replace(current, slide));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Replaces `current` with a slide fetched again from before the history, keeping `current`
+    /// to go forward to. Beyond `capacity`, the images farthest ahead are dropped, so going
+    /// forward continues with the fetched photos sooner
+    pub fn go_back_to(&mut self, current: &mut Slide, slide: Slide) {
+        let following = mem::replace(current, slide);
+        if self.capacity == 0 {
+            return;
+        }
+        if self.following.len() == self.capacity {
+            self.following.pop_front();
+        }
+        self.following.push_back(following);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(id: u32) -> Slide {
+        (DynamicImage::new_rgb8(id, 1), Some(format!("{id}.jpg")))
+    }
+
+    #[test]
+    fn history_keeps_at_most_capacity_images() {
+        let mut history = PhotoHistory::new(2);
+        (1..=3).for_each(|id| history.push(image(id)));
+        let mut current = image(4);
+
+        assert!(history.go_back(&mut current));
+        assert_eq!(current.0.width(), 3);
+        assert!(history.go_back(&mut current));
+        assert_eq!(current.0.width(), 2);
+        assert!(!history.go_back(&mut current));
+        assert_eq!(current.0.width(), 2);
+    }
+
+    #[test]
+    fn after_going_back_go_forward_returns_images_in_original_order() {
+        let mut history = PhotoHistory::new(3);
+        (1..=2).for_each(|id| history.push(image(id)));
+        let mut current = image(3);
+        history.go_back(&mut current);
+        history.go_back(&mut current);
+
+        assert!(history.go_forward(&mut current));
+        assert_eq!(current.0.width(), 2);
+        assert!(history.go_forward(&mut current));
+        assert_eq!(current.0.width(), 3);
+        assert!(!history.go_forward(&mut current));
+        assert!(history.go_back(&mut current));
+        assert_eq!(current.0.width(), 2);
+    }
+
+    #[test]
+    fn when_capacity_is_zero_then_nothing_is_kept() {
+        let mut history = PhotoHistory::new(0);
+        history.push(image(1));
+        let mut current = image(2);
+
+        assert!(!history.go_back(&mut current));
+    }
+
+    #[test]
+    fn slides_fetched_before_history_are_followed_by_at_most_capacity_images() {
+        let mut history = PhotoHistory::new(2);
+        let mut current = image(4);
+
+        history.go_back_to(&mut current, image(3));
+        history.go_back_to(&mut current, image(2));
+        history.go_back_to(&mut current, image(1));
+
+        assert_eq!(current.1.as_deref(), Some("1.jpg"));
+        assert!(!history.go_back(&mut current));
+        assert!(history.go_forward(&mut current));
+        assert_eq!(current.0.width(), 2);
+        assert!(history.go_forward(&mut current));
+        assert_eq!(current.0.width(), 3);
+        assert!(!history.go_forward(&mut current));
+    }
+}
@@ -0,0 +1,60 @@
+//! Shell commands run on slideshow events, e.g. to integrate with home automation
+
+use std::{
+    process::{Command, Stdio},
+    thread::{self, JoinHandle},
+};
+
+use crate::error::ErrorToString;
+
+/// Runs `command` with `sh -c` without waiting for it to finish. `value` (e.g. the file name of
+/// the displayed photo) is passed both as `$1` and as the environment variable `variable`.
+///
+/// Returns the thread logging the command output once it exits
+pub fn spawn(command: &str, variable: &str, value: &str) -> Result<JoinHandle<()>, String> {
+    let child = Command::new("sh")
+        .args(["-c", command, "sh", value])
+        .env(variable, value)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err_to_string()?;
+    let command = command.to_string();
+    Ok(thread::spawn(move || match child.wait_with_output() {
+        Ok(output) => {
+            for stream in [&output.stdout, &output.stderr] {
+                let text = String::from_utf8_lossy(stream);
+                if !text.trim().is_empty() {
+                    log::debug!("{command}: {}", text.trim_end());
+                }
+            }
+            if !output.status.success() {
+                log::warn!("{command}: {}", output.status);
+            }
+        }
+        Err(error) => log::warn!("{command}: {error}"),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    #[test]
+    fn spawned_command_gets_value_as_argument_and_environment_variable() {
+        let out_file = env::temp_dir().join(format!("hook_test_{}", std::process::id()));
+        let command = format!("echo \"$1 $FRAME_PHOTO\" > {}", out_file.display());
+
+        spawn(&command, "FRAME_PHOTO", "IMG_1.jpg")
+            .unwrap()
+            .join()
+            .unwrap();
+
+        let output = fs::read_to_string(&out_file).unwrap();
+        fs::remove_file(&out_file).unwrap();
+        assert_eq!(output, "IMG_1.jpg IMG_1.jpg\n");
+    }
+}
@@ -45,7 +45,7 @@ impl Client for ReqwestClient {
         form: &[(&str, &str)],
         header: Option<(&str, &str)>,
     ) -> Result<ReqwestResponse, String> {
-        let mut request_builder = ReqwestClient::builder().danger_accept_invalid_certs(true).build().unwrap().post(url).form(form);
+        let mut request_builder = ReqwestClient::post(self, url).form(form);
         if let Some((key, value)) = header {
             request_builder = request_builder.header(key, value);
         }
@@ -53,14 +53,10 @@ impl Client for ReqwestClient {
     }
 
     fn get(&self, url: &str, query: &[(&str, &str)]) -> Result<ReqwestResponse, String> {
-        ReqwestClient::builder().danger_accept_invalid_certs(true).build().unwrap().get(url)
-        .query(query)
-        .send()
-        .map_err_to_string()
-        // ReqwestClient::get(self, url)
-        //     .query(query)
-        //     .send()
-        //     .map_err_to_string()
+        ReqwestClient::get(self, url)
+            .query(query)
+            .send()
+            .map_err_to_string()
     }
 }
 
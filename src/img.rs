@@ -1,14 +1,48 @@
-use std::thread::{self, JoinHandle};
+use std::{
+    borrow::Cow,
+    io::Cursor,
+    num::NonZeroUsize,
+    thread::{self, JoinHandle},
+};
 
 pub use image::{open, DynamicImage};
 
 use image::{
     self,
     imageops::{self, FilterType},
-    GenericImageView,
+    GenericImageView, ImageReader, Luma, Rgb, RgbImage,
+};
+use qrcode::QrCode;
+
+use crate::{
+    cli::{Cli, Dither, FillMode, Layout, PixelFormat, ResizeFilter, Rotation, SourceSize},
+    error::ErrorToString,
+    layout::{self, Dimensions},
 };
 
-use crate::{cli::Rotation, error::ErrorToString};
+/// Settings of [Framed::fit_to_screen_and_add_background]
+#[derive(Clone, Copy, Debug)]
+pub struct FrameOptions {
+    pub rotation: Rotation,
+    /// Maximum number of threads processing a photo at the same time, including the calling
+    /// thread
+    pub threads: NonZeroUsize,
+    /// Maximum difference (in percent) between the image and screen aspect ratios for which the
+    /// image is stretched to fill the screen instead of getting a background
+    pub stretch_threshold: f64,
+    /// Filter used to resize the image to the screen size
+    pub resize_filter: FilterType,
+    pub fill_mode: FillMode,
+    /// Keep images smaller than the screen at their original size instead of enlarging them
+    pub no_upscale: bool,
+    /// Landscape dimensions images are shrunk to fit right after decoding
+    pub max_resolution: Option<(u32, u32)>,
+    /// Sigma of the unsharp mask applied to the resized foreground, not applied when None
+    pub sharpen: Option<f32>,
+    /// Width in pixels and color of the border between the photo and the background, none when
+    /// None
+    pub matte: Option<(u32, (u8, u8, u8))>,
+}
 
 pub trait Framed {
     /// Resizes an image while preserving the aspect ratio, and centers it on screen. Returns a new
@@ -17,37 +51,82 @@ pub trait Framed {
 
     /// Resizes an image while preserving the aspect ratio, and centers it on screen, filling any
     /// empty space with blurred background
-    fn fit_to_screen_and_add_background(&self, screen_size: (u32, u32), rotation: Rotation)
-        -> Self;
+    fn fit_to_screen_and_add_background(
+        &self,
+        screen_size: (u32, u32),
+        options: &FrameOptions,
+    ) -> Self;
+
+    /// Shrinks an image preserving the aspect ratio so that it fits the screen once rotated.
+    /// Images that already fit are returned unchanged
+    fn downscale_to_screen(&self, screen_size: (u32, u32), options: &FrameOptions) -> Self;
+
+    /// Shrinks an image preserving the aspect ratio so that it fits `max_resolution`, swapped for
+    /// portrait images. Images that already fit are returned unchanged
+    fn limit_resolution(&self, max_resolution: (u32, u32), options: &FrameOptions) -> Self;
 
     /// Adds update icon to an image
     fn overlay_update_icon(&mut self, update_icon: &Self, rotation: Rotation);
 
-    fn resize(&self, new_width: u32, new_height: u32) -> Self;
+    /// Adds a QR code to the corner of an image opposite to the update icon
+    fn overlay_qr_code(&mut self, qr_code: &Self, rotation: Rotation);
 
+    /// Rotates an image. [Rotation::Auto] needs to be resolved with [Rotation::resolve] first,
+    /// otherwise the image is not rotated
     fn rotate(&self, degrees: Rotation) -> Self;
 }
 
 impl Framed for DynamicImage {
     fn fit_to_screen(&self, screen_size: (u32, u32), rotation: Rotation) -> Self {
-        let rotated = self.rotate(rotation);
-        let resized = resize_to_fit_screen(&rotated, screen_size);
+        let rotated = self.rotate(rotation.resolve(self.dimensions(), screen_size));
+        let resized = resize_to_fit_screen(&rotated, screen_size, 0.0, FilterType::Lanczos3, true);
+        if resized.dimensions() == screen_size {
+            return resized;
+        }
         center_on_screen(&resized, screen_size)
     }
 
-    fn fit_to_screen_and_add_background(&self, screen_size: (u32, u32), rotate: Rotation) -> Self {
+    fn fit_to_screen_and_add_background(
+        &self,
+        screen_size: (u32, u32),
+        options: &FrameOptions,
+    ) -> Self {
         internal_fit_to_screen_and_add_background(
             self,
             screen_size,
-            rotate,
+            options,
             brighten_and_blur_background,
         )
     }
 
+    fn downscale_to_screen(&self, (x_res, y_res): (u32, u32), options: &FrameOptions) -> Self {
+        let (w_max, h_max) = match options.rotation.resolve(self.dimensions(), (x_res, y_res)) {
+            Rotation::D90 | Rotation::D270 => (y_res, x_res),
+            Rotation::D0 | Rotation::D180 | Rotation::Auto => (x_res, y_res),
+        };
+        if self.width() <= w_max && self.height() <= h_max {
+            return self.to_owned();
+        }
+        self.resize(w_max, h_max, options.resize_filter)
+    }
+
+    fn limit_resolution(&self, (w_max, h_max): (u32, u32), options: &FrameOptions) -> Self {
+        let (long_max, short_max) = (w_max.max(h_max), w_max.min(h_max));
+        let (w_max, h_max) = if self.width() >= self.height() {
+            (long_max, short_max)
+        } else {
+            (short_max, long_max)
+        };
+        if self.width() <= w_max && self.height() <= h_max {
+            return self.to_owned();
+        }
+        self.resize(w_max, h_max, options.resize_filter)
+    }
+
     fn overlay_update_icon(&mut self, update_icon: &Self, rotation: Rotation) {
         let (width, height) = (self.width(), self.height());
         let (x_offset, y_offset) = match rotation {
-            Rotation::D0 => (0, 0),
+            Rotation::D0 | Rotation::Auto => (0, 0),
             Rotation::D90 => (width - update_icon.height(), 0),
             Rotation::D180 => (width - update_icon.width(), height - update_icon.height()),
             Rotation::D270 => (0, height - update_icon.width()),
@@ -55,13 +134,20 @@ impl Framed for DynamicImage {
         imageops::overlay(self, update_icon, x_offset as i64, y_offset as i64);
     }
 
-    fn resize(&self, new_width: u32, new_height: u32) -> Self {
-        self.resize(new_width, new_height, FilterType::Lanczos3)
+    fn overlay_qr_code(&mut self, qr_code: &Self, rotation: Rotation) {
+        let (width, height) = (self.width(), self.height());
+        let (x_offset, y_offset) = match rotation {
+            Rotation::D0 | Rotation::Auto => (width - qr_code.width(), height - qr_code.height()),
+            Rotation::D90 => (0, height - qr_code.width()),
+            Rotation::D180 => (0, 0),
+            Rotation::D270 => (width - qr_code.height(), 0),
+        };
+        imageops::overlay(self, qr_code, x_offset as i64, y_offset as i64);
     }
 
     fn rotate(&self, degrees: Rotation) -> Self {
         match degrees {
-            Rotation::D0 => self.to_owned(),
+            Rotation::D0 | Rotation::Auto => self.to_owned(),
             Rotation::D90 => self.rotate90(),
             Rotation::D180 => self.rotate180(),
             Rotation::D270 => self.rotate270(),
@@ -69,36 +155,327 @@ impl Framed for DynamicImage {
     }
 }
 
+impl Rotation {
+    /// Resolves [Rotation::Auto] to a fixed rotation for an image of given dimensions, so that
+    /// its orientation (portrait or landscape) matches the screen. Other variants are returned
+    /// unchanged
+    pub fn resolve(self, (w, h): (u32, u32), (x_res, y_res): (u32, u32)) -> Rotation {
+        match self {
+            Rotation::Auto if (w > h && x_res < y_res) || (w < h && x_res > y_res) => Rotation::D90,
+            Rotation::Auto => Rotation::D0,
+            fixed => fixed,
+        }
+    }
+}
+
+impl SourceSize {
+    /// Maximum width and height of a landscape photo
+    pub fn dimensions(self) -> (u32, u32) {
+        match self {
+            SourceSize::S => (360, 240),
+            SourceSize::M => (481, 320),
+            SourceSize::L => (1922, 1280),
+        }
+    }
+}
+
+impl Layout {
+    /// Number of columns and rows of photos on screen
+    pub fn grid(self) -> (u32, u32) {
+        match self {
+            Layout::Single => (1, 1),
+            Layout::Grid2x2 => (2, 2),
+        }
+    }
+}
+
+/// Places tiles in a grid of equally sized cells, row by row. Cells without a tile are left
+/// black
+pub fn compose_grid(
+    tiles: &[DynamicImage],
+    (x_res, y_res): (u32, u32),
+    (columns, rows): (u32, u32),
+) -> DynamicImage {
+    let (cell_w, cell_h) = (x_res / columns, y_res / rows);
+    let mut final_image = DynamicImage::new_rgb8(x_res, y_res);
+    for (index, tile) in (0..columns * rows).zip(tiles) {
+        imageops::overlay(
+            &mut final_image,
+            tile,
+            ((index % columns) * cell_w).into(),
+            ((index / columns) * cell_h).into(),
+        );
+    }
+    final_image
+}
+
+/// Renders `data` as a black on white QR code, at least `size` pixels wide and high including
+/// the quiet zone around it
+pub fn qr_code(data: &str, size: u32) -> Result<DynamicImage, String> {
+    let code = QrCode::new(data.as_bytes()).map_err_to_string()?;
+    let image = code.render::<Luma<u8>>().min_dimensions(size, size).build();
+    Ok(DynamicImage::ImageLuma8(image))
+}
+
+/// Reduces each color channel to `bits` bits, dithering to hide banding
+pub fn dither(image: DynamicImage, method: Dither, bits: u8) -> DynamicImage {
+    if method == Dither::None {
+        return image;
+    }
+    let mut rgb_image = image.into_rgb8();
+    let step = 255.0 / ((1_u32 << bits) - 1) as f32;
+    let quantize = |value: f32| ((value / step).round() * step).clamp(0.0, 255.0);
+    match method {
+        Dither::None => (),
+        Dither::Ordered => {
+            const BAYER_4X4: [[u8; 4]; 4] =
+                [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+            for (x, y, pixel) in rgb_image.enumerate_pixels_mut() {
+                let threshold =
+                    (BAYER_4X4[y as usize % 4][x as usize % 4] as f32 + 0.5) / 16.0 - 0.5;
+                for channel in pixel.0.iter_mut() {
+                    *channel = quantize(*channel as f32 + threshold * step) as u8;
+                }
+            }
+        }
+        Dither::FloydSteinberg => {
+            let (w, h) = (rgb_image.width() as usize, rgb_image.height() as usize);
+            let mut values: Vec<f32> = rgb_image.as_raw().iter().map(|&v| v as f32).collect();
+            /* Quantization error of each channel is diffused to the pixels not processed yet */
+            let diffuse = |values: &mut [f32], x: isize, y: usize, channel: usize, error: f32| {
+                if (0..w as isize).contains(&x) && y < h {
+                    values[(y * w + x as usize) * 3 + channel] += error;
+                }
+            };
+            for y in 0..h {
+                for x in 0..w {
+                    for channel in 0..3 {
+                        let index = (y * w + x) * 3 + channel;
+                        let old = values[index];
+                        let new = quantize(old);
+                        values[index] = new;
+                        let error = old - new;
+                        let x = x as isize;
+                        diffuse(&mut values, x + 1, y, channel, error * 7.0 / 16.0);
+                        diffuse(&mut values, x - 1, y + 1, channel, error * 3.0 / 16.0);
+                        diffuse(&mut values, x, y + 1, channel, error * 5.0 / 16.0);
+                        diffuse(&mut values, x + 1, y + 1, channel, error / 16.0);
+                    }
+                }
+            }
+            for (channel, value) in rgb_image.iter_mut().zip(values) {
+                *channel = value as u8;
+            }
+        }
+    }
+    DynamicImage::ImageRgb8(rgb_image)
+}
+
+/// Returns the pixel data of an image in the byte layout of a texture with the given format
+pub fn pixel_bytes(image: &DynamicImage, format: PixelFormat) -> Cow<'_, [u8]> {
+    match (format, image) {
+        (PixelFormat::Rgb24, DynamicImage::ImageRgb8(rgb_image)) => Cow::Borrowed(rgb_image),
+        (PixelFormat::Rgb24, _) => Cow::Owned(image.to_rgb8().into_raw()),
+        (PixelFormat::Bgr24, _) => Cow::Owned(
+            image
+                .to_rgb8()
+                .pixels()
+                .flat_map(|&Rgb([r, g, b])| [b, g, r])
+                .collect(),
+        ),
+        (PixelFormat::Rgba, _) => Cow::Owned(image.to_rgba8().into_raw()),
+    }
+}
+
+impl From<&Cli> for FrameOptions {
+    fn from(cli: &Cli) -> Self {
+        FrameOptions {
+            rotation: cli.rotation,
+            threads: cli
+                .decode_threads
+                .and_then(|threads| NonZeroUsize::new(threads.into()))
+                .unwrap_or_else(available_threads),
+            stretch_threshold: cli.stretch_threshold,
+            resize_filter: cli.resize_filter.into(),
+            fill_mode: cli.fill_mode,
+            no_upscale: cli.no_upscale,
+            max_resolution: cli
+                .max_resolution
+                .or(cli.source_size.map(SourceSize::dimensions)),
+            sharpen: cli.sharpen,
+            matte: (cli.matte_width > 0).then_some((cli.matte_width, cli.matte_color)),
+        }
+    }
+}
+
+impl Default for FrameOptions {
+    fn default() -> Self {
+        FrameOptions {
+            rotation: Rotation::D0,
+            threads: available_threads(),
+            stretch_threshold: 0.0,
+            resize_filter: FilterType::Lanczos3,
+            fill_mode: FillMode::Blur,
+            no_upscale: false,
+            max_resolution: None,
+            sharpen: None,
+            matte: None,
+        }
+    }
+}
+
+impl From<ResizeFilter> for FilterType {
+    fn from(value: ResizeFilter) -> Self {
+        match value {
+            ResizeFilter::Nearest => FilterType::Nearest,
+            ResizeFilter::Triangle => FilterType::Triangle,
+            ResizeFilter::CatmullRom => FilterType::CatmullRom,
+            ResizeFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+fn available_threads() -> NonZeroUsize {
+    thread::available_parallelism().unwrap_or(NonZeroUsize::MIN)
+}
+
+#[cfg(not(feature = "jpeg-decoder"))]
 pub fn load_from_memory(buffer: &[u8]) -> Result<DynamicImage, String> {
     image::load_from_memory(buffer).map_err_to_string()
 }
 
+/// Names and file extensions of the photo formats that can be decoded, one format per line
+pub fn supported_formats() -> Vec<String> {
+    image::ImageFormat::all()
+        .filter(|format| format.reading_enabled())
+        .map(|format| {
+            let fallback = if cfg!(feature = "jpeg-decoder") && format == image::ImageFormat::Jpeg {
+                " with jpeg-decoder fallback"
+            } else {
+                ""
+            };
+            format!(
+                "{format:?}: {}{fallback}",
+                format.extensions_str().join(", ")
+            )
+        })
+        .collect()
+}
+
+/// Reads image width and height from the header, decoding the whole image only when the header
+/// can't be read
+pub fn dimensions(buffer: &[u8]) -> Result<(u32, u32), String> {
+    ImageReader::new(Cursor::new(buffer))
+        .with_guessed_format()
+        .map_err_to_string()
+        .and_then(|reader| reader.into_dimensions().map_err_to_string())
+        .or_else(|_| load_from_memory(buffer).map(|image| image.dimensions()))
+}
+
+/// Decodes an image, falling back to [jpeg_decoder] for JPEG files rejected by [image] (e.g.
+/// some CMYK files)
+#[cfg(feature = "jpeg-decoder")]
+pub fn load_from_memory(buffer: &[u8]) -> Result<DynamicImage, String> {
+    image::load_from_memory(buffer).or_else(|error| {
+        log::debug!("Decoding failed, trying jpeg-decoder: {error}");
+        jpeg_decoder_load_from_memory(buffer)
+            .map_err(|fallback_error| format!("image: {error}; jpeg-decoder: {fallback_error}"))
+    })
+}
+
+#[cfg(feature = "jpeg-decoder")]
+fn jpeg_decoder_load_from_memory(buffer: &[u8]) -> Result<DynamicImage, String> {
+    use image::{GrayImage, ImageBuffer, Luma, RgbImage};
+    use jpeg_decoder::{Decoder, PixelFormat};
+
+    let mut decoder = Decoder::new(buffer);
+    let pixels = decoder.decode().map_err_to_string()?;
+    let info = decoder.info().ok_or("missing image info")?;
+    let (w, h) = (info.width.into(), info.height.into());
+    let image = match info.pixel_format {
+        PixelFormat::L8 => GrayImage::from_raw(w, h, pixels).map(DynamicImage::ImageLuma8),
+        PixelFormat::L16 => {
+            let pixels = pixels
+                .chunks_exact(2)
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+                .collect();
+            ImageBuffer::<Luma<u16>, _>::from_raw(w, h, pixels).map(DynamicImage::ImageLuma16)
+        }
+        PixelFormat::RGB24 => RgbImage::from_raw(w, h, pixels).map(DynamicImage::ImageRgb8),
+        PixelFormat::CMYK32 => {
+            RgbImage::from_raw(w, h, cmyk_to_rgb(&pixels)).map(DynamicImage::ImageRgb8)
+        }
+    };
+    image.ok_or_else(|| "decoded pixels do not match image size".to_string())
+}
+
+/// Converts inverted CMYK pixels (as stored by Adobe applications) to RGB
+#[cfg(feature = "jpeg-decoder")]
+fn cmyk_to_rgb(cmyk: &[u8]) -> Vec<u8> {
+    cmyk.chunks_exact(4)
+        .flat_map(|pixel| {
+            let k = u16::from(pixel[3]);
+            [pixel[0], pixel[1], pixel[2]].map(|cmy| (u16::from(cmy) * k / 255) as u8)
+        })
+        .collect()
+}
+
+/// Minimum brightness difference the unsharp mask enhances, leaving noise in flat areas alone
+const SHARPEN_THRESHOLD: i32 = 2;
+
 /// Testable version of [Framed::fit_to_screen_and_add_background]
 fn internal_fit_to_screen_and_add_background(
     original: &DynamicImage,
     screen_size: (u32, u32),
-    rotate: Rotation,
+    options: &FrameOptions,
     brighten_and_blur: fn(&DynamicImage) -> DynamicImage,
 ) -> DynamicImage {
-    let rotated = original.rotate(rotate);
+    let rotated = original.rotate(options.rotation.resolve(original.dimensions(), screen_size));
+    if let Some(matte) = options.matte {
+        return fit_to_screen_with_matte(&rotated, screen_size, matte, options, brighten_and_blur);
+    }
     if rotated.dimensions() == screen_size {
         return rotated;
     }
+    let (x_res, y_res) = screen_size;
+    if options.no_upscale && rotated.width() <= x_res && rotated.height() <= y_res {
+        /* The photo does not reach any screen edge, so the background covers the whole screen */
+        let mut final_image =
+            full_screen_fill(&rotated, screen_size, options.fill_mode, brighten_and_blur);
+        imageops::overlay(
+            &mut final_image,
+            &rotated,
+            ((x_res - rotated.width()) / 2).into(),
+            ((y_res - rotated.height()) / 2).into(),
+        );
+        return final_image;
+    }
 
-    let (bg_thread1, bg_thread2) =
-        background_fill_threads(&rotated, screen_size, brighten_and_blur);
-    let foreground = resize_to_fit_screen(&rotated, screen_size);
+    let (bg_fill1, bg_fill2) = background_fills(
+        &rotated,
+        screen_size,
+        options.fill_mode,
+        brighten_and_blur,
+        options.threads,
+    );
+    let foreground = resize_to_fit_screen(
+        &rotated,
+        screen_size,
+        options.stretch_threshold,
+        options.resize_filter,
+        !options.no_upscale,
+    );
+    let foreground = sharpen_resized(foreground, rotated.dimensions(), options);
     if foreground.dimensions() == screen_size {
         return foreground;
     }
 
-    let (x_res, y_res) = screen_size;
     let mut final_image = DynamicImage::new_rgb8(x_res, y_res);
 
-    let bg_fill_1 = bg_thread1.join().unwrap();
+    let bg_fill_1 = bg_fill1.join();
     imageops::overlay(&mut final_image, &bg_fill_1, 0, 0);
 
-    let bg_fill_2 = bg_thread2.join().unwrap();
+    let bg_fill_2 = bg_fill2.join();
     imageops::overlay(
         &mut final_image,
         &bg_fill_2,
@@ -117,19 +494,92 @@ fn internal_fit_to_screen_and_add_background(
     final_image
 }
 
-fn resize_to_fit_screen(original: &DynamicImage, (x_res, y_res): (u32, u32)) -> DynamicImage {
+/// Applies --sharpen to a foreground resized from `original_dimensions`
+fn sharpen_resized(
+    foreground: DynamicImage,
+    original_dimensions: (u32, u32),
+    options: &FrameOptions,
+) -> DynamicImage {
+    match options.sharpen {
+        /* Only resizing softens the image */
+        Some(sigma) if foreground.dimensions() != original_dimensions => {
+            foreground.unsharpen(sigma, SHARPEN_THRESHOLD)
+        }
+        _ => foreground,
+    }
+}
+
+/// Fits the photo within the screen less the matte on each side, and centers it surrounded by
+/// the matte on a background covering the whole screen
+fn fit_to_screen_with_matte(
+    rotated: &DynamicImage,
+    screen_size: (u32, u32),
+    (matte_width, (r, g, b)): (u32, (u8, u8, u8)),
+    options: &FrameOptions,
+    brighten_and_blur: fn(&DynamicImage) -> DynamicImage,
+) -> DynamicImage {
+    let (x_res, y_res) = screen_size;
+    let inset_size = (
+        x_res.saturating_sub(2 * matte_width).max(1),
+        y_res.saturating_sub(2 * matte_width).max(1),
+    );
+    let foreground = resize_to_fit_screen(
+        rotated,
+        inset_size,
+        options.stretch_threshold,
+        options.resize_filter,
+        !options.no_upscale,
+    );
+    let foreground = sharpen_resized(foreground, rotated.dimensions(), options);
+    let mut final_image =
+        full_screen_fill(rotated, screen_size, options.fill_mode, brighten_and_blur);
+    let (w, h) = foreground.dimensions();
+    let matte = RgbImage::from_pixel(w + 2 * matte_width, h + 2 * matte_width, Rgb([r, g, b]));
+    let (x, y) = (
+        i64::from(x_res.saturating_sub(w) / 2),
+        i64::from(y_res.saturating_sub(h) / 2),
+    );
+    let matte_offset = i64::from(matte_width);
+    imageops::overlay(
+        &mut final_image,
+        &DynamicImage::ImageRgb8(matte),
+        x - matte_offset,
+        y - matte_offset,
+    );
+    imageops::overlay(&mut final_image, &foreground, x, y);
+    final_image
+}
+
+fn resize_to_fit_screen(
+    original: &DynamicImage,
+    (x_res, y_res): (u32, u32),
+    stretch_threshold: f64,
+    filter: FilterType,
+    upscale: bool,
+) -> DynamicImage {
     let original_dimensions = Dimensions::from(original.dimensions());
     let screen_dimensions = Dimensions::from((x_res, y_res));
     let foreground_dimensions = original_dimensions.resize(screen_dimensions);
 
+    if !upscale && original.width() <= x_res && original.height() <= y_res {
+        /* Scale factor is clamped at 1 */
+        return original.clone();
+    }
+
     if foreground_dimensions.is_exact_fit_to(screen_dimensions) {
         /* Image fits perfectly, background not needed. Note that this may still stretch the image
          * by one pixel horizontally or vertically to make a perfect fit when resized dimensions
          * are off by a fraction. */
-        return original.resize_exact(x_res, y_res, FilterType::Lanczos3);
+        return original.resize_exact(x_res, y_res, filter);
     }
 
-    Framed::resize(original, x_res, y_res)
+    if original_dimensions.aspect_ratio_difference(screen_dimensions) * 100.0 < stretch_threshold {
+        /* Aspect ratios are close enough to stretch the image rather than add thin bars of
+         * background */
+        return original.resize_exact(x_res, y_res, filter);
+    }
+
+    original.resize(x_res, y_res, filter)
 }
 
 fn center_on_screen(original: &DynamicImage, (x_res, y_res): (u32, u32)) -> DynamicImage {
@@ -150,170 +600,154 @@ fn center_on_screen(original: &DynamicImage, (x_res, y_res): (u32, u32)) -> Dyna
     final_image
 }
 
-fn background_fill_threads(
+/// Starts creating the two background fills. Each one gets a separate thread as long as
+/// `threads` allows, otherwise it is created when joined.
+fn background_fills(
     image: &DynamicImage,
-    (x_res, y_res): (u32, u32),
+    screen_size: (u32, u32),
+    fill_mode: FillMode,
     brighten_and_blur: fn(&DynamicImage) -> DynamicImage,
-) -> (JoinHandle<DynamicImage>, JoinHandle<DynamicImage>) {
+    threads: NonZeroUsize,
+) -> (BackgroundFill, BackgroundFill) {
     let original_dimensions = Dimensions::from(image.dimensions());
-    let screen_dimensions = Dimensions::from((x_res, y_res));
-    let (
-        Coords {
-            x: x1,
-            y: y1,
-            w: w1,
-            h: h1,
-        },
-        Coords {
-            x: x2,
-            y: y2,
-            w: w2,
-            h: h2,
-        },
-    ) = original_dimensions.background_crops(screen_dimensions);
+    let screen_dimensions = Dimensions::from(screen_size);
+    let (crop1, crop2) = layout::background_crops(image.dimensions(), screen_size);
     let (bg_crop1, bg_crop2) = (
-        image.crop_imm(
-            x1.floor() as u32,
-            y1.floor() as u32,
-            w1.ceil() as u32,
-            h1.ceil() as u32,
-        ),
-        image.crop_imm(
-            x2.floor() as u32,
-            y2.floor() as u32,
-            w2.ceil() as u32,
-            h2.ceil() as u32,
-        ),
+        image.crop_imm(crop1.x, crop1.y, crop1.w, crop1.h),
+        image.crop_imm(crop2.x, crop2.y, crop2.w, crop2.h),
     );
-    let bg_thread1 = thread::spawn(move || {
-        let bg = bg_crop1.resize(x_res, y_res, FilterType::Nearest);
-        brighten_and_blur(&bg)
-    });
-    let bg_thread2 = thread::spawn(move || {
-        let bg = bg_crop2.resize(x_res, y_res, FilterType::Nearest);
-        brighten_and_blur(&bg)
-    });
-    (bg_thread1, bg_thread2)
-}
-
-fn brighten_and_blur_background(background: &DynamicImage) -> DynamicImage {
-    const BRIGHTNESS_OFFSET: i32 = -20;
-    const BLUR_SIGMA: f32 = 45.0;
-    background.brighten(BRIGHTNESS_OFFSET).blur(BLUR_SIGMA)
+    let (bg_fill1, bg_fill2) = match fill_mode {
+        FillMode::Blur => (
+            blurred_fill(bg_crop1, screen_size, brighten_and_blur),
+            blurred_fill(bg_crop2, screen_size, brighten_and_blur),
+        ),
+        FillMode::EdgeAverage => {
+            /* The outer edge of each crop is the edge of the photo next to the fill */
+            let fills_left_and_right =
+                original_dimensions.aspect_ratio() < screen_dimensions.aspect_ratio();
+            let (edge1, edge2) = if fills_left_and_right {
+                (Edge::Left, Edge::Right)
+            } else {
+                (Edge::Top, Edge::Bottom)
+            };
+            (
+                edge_average_fill(bg_crop1, screen_size, edge1),
+                edge_average_fill(bg_crop2, screen_size, edge2),
+            )
+        }
+    };
+    /* The calling thread is busy resizing the foreground in the meantime */
+    let spare_threads = threads.get() - 1;
+    (
+        BackgroundFill::new(bg_fill1, spare_threads >= 1),
+        BackgroundFill::new(bg_fill2, spare_threads >= 2),
+    )
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Dimensions {
-    w: f64,
-    h: f64,
-}
+type Fill = Box<dyn FnOnce() -> DynamicImage + Send>;
 
-impl From<(u32, u32)> for Dimensions {
-    fn from((w, h): (u32, u32)) -> Self {
-        Self {
-            w: w as f64,
-            h: h as f64,
+/// Background covering the whole screen, made of the whole image
+fn full_screen_fill(
+    image: &DynamicImage,
+    (x_res, y_res): (u32, u32),
+    fill_mode: FillMode,
+    brighten_and_blur: fn(&DynamicImage) -> DynamicImage,
+) -> DynamicImage {
+    match fill_mode {
+        FillMode::Blur => {
+            brighten_and_blur(&image.resize_to_fill(x_res, y_res, FilterType::Nearest))
+        }
+        FillMode::EdgeAverage => {
+            let rgb_image = image.to_rgb8();
+            let mut sums = [0_u64; 3];
+            for pixel in rgb_image.pixels() {
+                for (sum, channel) in sums.iter_mut().zip(pixel.0) {
+                    *sum += u64::from(channel);
+                }
+            }
+            let count = u64::from(rgb_image.width() * rgb_image.height()).max(1);
+            let mean = Rgb(sums.map(|sum| (sum / count) as u8));
+            DynamicImage::ImageRgb8(RgbImage::from_pixel(x_res, y_res, mean))
         }
     }
 }
 
-impl Dimensions {
-    const fn new(w: f64, h: f64) -> Self {
-        Self { w, h }
-    }
-
-    fn diff(self, Dimensions { w, h }: Dimensions) -> (f64, f64) {
-        (f64::abs(self.w - w), f64::abs(self.h - h))
-    }
-
-    fn is_exact_fit_to(self, target: Dimensions) -> bool {
-        let (w_diff, h_diff) = self.diff(target);
-        w_diff as u32 == 0 && h_diff as u32 == 0
-    }
+fn blurred_fill(
+    crop: DynamicImage,
+    (x_res, y_res): (u32, u32),
+    brighten_and_blur: fn(&DynamicImage) -> DynamicImage,
+) -> Fill {
+    Box::new(move || {
+        let bg = crop.resize(x_res, y_res, FilterType::Nearest);
+        brighten_and_blur(&bg)
+    })
+}
 
-    /// Resize dimensions preserving aspect ratio. The dimensions are scaled to the maximum possible
-    /// size that fits within the bounds specified by `new_width` and `new_height`.
-    fn resize(
-        self,
-        Dimensions {
-            w: new_width,
-            h: new_height,
-        }: Dimensions,
-    ) -> Dimensions {
-        let w_ratio = new_width / self.w;
-        let h_ratio = new_height / self.h;
+/// Fills the screen with the mean color of pixels along an edge of the crop
+fn edge_average_fill(crop: DynamicImage, (x_res, y_res): (u32, u32), edge: Edge) -> Fill {
+    Box::new(move || {
+        let (w, h) = crop.dimensions();
+        let edge_pixels = match edge {
+            Edge::Left => crop.view(0, 0, 1, h),
+            Edge::Right => crop.view(w - 1, 0, 1, h),
+            Edge::Top => crop.view(0, 0, w, 1),
+            Edge::Bottom => crop.view(0, h - 1, w, 1),
+        };
+        let mut sums = [0_u64; 3];
+        for (_, _, pixel) in edge_pixels.pixels() {
+            for (sum, channel) in sums.iter_mut().zip(pixel.0) {
+                *sum += u64::from(channel);
+            }
+        }
+        let count = u64::from(edge_pixels.width() * edge_pixels.height()).max(1);
+        let mean = Rgb(sums.map(|sum| (sum / count) as u8));
+        /* Same dimensions as a blurred fill */
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(w, h, mean)).resize(
+            x_res,
+            y_res,
+            FilterType::Nearest,
+        )
+    })
+}
 
-        let ratio = f64::min(w_ratio, h_ratio);
+#[derive(Clone, Copy, Debug)]
+enum Edge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
 
-        let nw = f64::max(self.w * ratio, 1.0);
-        let nh = f64::max(self.h * ratio, 1.0);
+enum BackgroundFill {
+    Spawned(JoinHandle<DynamicImage>),
+    Deferred(Fill),
+}
 
-        Dimensions::new(nw, nh)
+impl BackgroundFill {
+    fn new(fill: Fill, spawn: bool) -> Self {
+        if spawn {
+            BackgroundFill::Spawned(thread::spawn(fill))
+        } else {
+            BackgroundFill::Deferred(fill)
+        }
     }
 
-    /// Calculates coordinates of parts of the foreground that will form the background fills.
-    fn background_crops(self, screen_size: Dimensions) -> (Coords, Coords) {
-        let screen_to_image_projection = screen_size.resize(self);
-        let (w_diff, h_diff) = screen_to_image_projection.diff(self);
-        let (bg_x, bg_y) = (w_diff / 2.0, h_diff / 2.0);
-
-        let image_to_projected_screen = self.resize(screen_to_image_projection);
-        let (w_diff, h_diff) = image_to_projected_screen.diff(screen_to_image_projection);
-
-        let Dimensions {
-            w: screen_w,
-            h: screen_h,
-        } = screen_to_image_projection;
-
-        if w_diff > 0.0 {
-            /* Needs background on left and right. */
-            let bg_w = w_diff / 2.0;
-            (
-                Coords {
-                    x: bg_x,
-                    y: bg_y,
-                    w: bg_w,
-                    h: screen_h,
-                },
-                Coords {
-                    x: self.w - bg_w,
-                    y: bg_y,
-                    w: bg_w,
-                    h: screen_h,
-                },
-            )
-        } else {
-            /* Needs background on top and bottom .*/
-            let bg_h = h_diff / 2.0;
-            (
-                Coords {
-                    x: bg_x,
-                    y: bg_y,
-                    w: screen_w,
-                    h: bg_h,
-                },
-                Coords {
-                    x: bg_x,
-                    y: self.h - bg_h,
-                    w: screen_w,
-                    h: bg_h,
-                },
-            )
+    fn join(self) -> DynamicImage {
+        match self {
+            BackgroundFill::Spawned(thread) => thread.join().unwrap(),
+            BackgroundFill::Deferred(fill) => fill(),
         }
     }
 }
 
-#[derive(Debug)]
-struct Coords {
-    x: f64,
-    y: f64,
-    w: f64,
-    h: f64,
+fn brighten_and_blur_background(background: &DynamicImage) -> DynamicImage {
+    const BRIGHTNESS_OFFSET: i32 = -20;
+    const BLUR_SIGMA: f32 = 45.0;
+    background.brighten(BRIGHTNESS_OFFSET).blur(BLUR_SIGMA)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::cli::Rotation;
     use image::{GenericImage, GenericImageView, Rgba};
 
     use super::*;
@@ -331,7 +765,7 @@ mod tests {
         let result = internal_fit_to_screen_and_add_background(
             &original,
             screen,
-            Rotation::D0,
+            &FrameOptions::default(),
             panicking_brighten_and_blur_stub,
         );
 
@@ -348,7 +782,7 @@ mod tests {
         let result = internal_fit_to_screen_and_add_background(
             &original,
             screen,
-            Rotation::D0,
+            &FrameOptions::default(),
             panicking_brighten_and_blur_stub,
         );
 
@@ -378,7 +812,7 @@ mod tests {
         let result = internal_fit_to_screen_and_add_background(
             &original,
             (x_res, y_res),
-            Rotation::D0,
+            &FrameOptions::default(),
             brighten_and_blur_stub,
         );
 
@@ -418,7 +852,7 @@ mod tests {
         let result = internal_fit_to_screen_and_add_background(
             &original,
             (x_res, y_res),
-            Rotation::D0,
+            &FrameOptions::default(),
             brighten_and_blur_stub,
         );
 
@@ -458,7 +892,7 @@ mod tests {
         let result = internal_fit_to_screen_and_add_background(
             &original,
             (x_res, y_res),
-            Rotation::D0,
+            &FrameOptions::default(),
             brighten_and_blur_stub,
         );
 
@@ -498,7 +932,7 @@ mod tests {
         let result = internal_fit_to_screen_and_add_background(
             &original,
             (x_res, y_res),
-            Rotation::D0,
+            &FrameOptions::default(),
             brighten_and_blur_stub,
         );
 
@@ -516,6 +950,440 @@ mod tests {
         }
     }
 
+    #[test]
+    fn when_aspect_ratio_difference_is_below_stretch_threshold_then_image_is_stretched() {
+        let pixel = Rgba([1, 2, 3, 255]);
+        /* Aspect ratio differs by 2.7% */
+        let original = create_test_image((100, 60), pixel);
+        let screen = (120, 70);
+        let options = FrameOptions {
+            stretch_threshold: 3.0,
+            ..FrameOptions::default()
+        };
+
+        let result = internal_fit_to_screen_and_add_background(
+            &original,
+            screen,
+            &options,
+            panicking_brighten_and_blur_stub,
+        );
+
+        assert_eq!(result.dimensions(), screen);
+        assert!(result.pixels().all(|(_, _, p)| p == pixel));
+    }
+
+    #[test]
+    fn when_aspect_ratio_difference_exceeds_stretch_threshold_then_background_is_created() {
+        let original = create_test_image((100, 60), RED);
+        let screen = (120, 70);
+        let options = FrameOptions {
+            stretch_threshold: 2.0,
+            ..FrameOptions::default()
+        };
+        fn brighten_and_blur_stub(img: &DynamicImage) -> DynamicImage {
+            img.brighten(-55)
+        }
+
+        let result = internal_fit_to_screen_and_add_background(
+            &original,
+            screen,
+            &options,
+            brighten_and_blur_stub,
+        );
+
+        assert_eq!(result.get_pixel(0, 0), Rgba([200, 0, 0, 255]));
+    }
+
+    #[test]
+    fn when_edge_average_fill_mode_then_background_has_mean_color_of_adjacent_edge() {
+        let mut original = create_test_image((50, 40), RED);
+        for y in 0..40 {
+            /* Left edge averages to (0, 127, 127) */
+            original.put_pixel(0, y, if y % 2 == 0 { GREEN } else { BLUE });
+            original.put_pixel(49, y, BLUE);
+        }
+        let screen = (120, 80);
+        let options = FrameOptions {
+            fill_mode: FillMode::EdgeAverage,
+            ..FrameOptions::default()
+        };
+
+        let result = internal_fit_to_screen_and_add_background(
+            &original,
+            screen,
+            &options,
+            panicking_brighten_and_blur_stub,
+        );
+
+        for y in 0..80 {
+            assert_eq!(result.get_pixel(0, y), Rgba([0, 127, 127, 255]));
+            assert_eq!(result.get_pixel(119, y), BLUE);
+        }
+    }
+
+    #[test]
+    fn downscale_to_screen_shrinks_only_images_larger_than_screen() {
+        let screen = (120, 80);
+        let options = FrameOptions::default();
+
+        let large = create_test_image((600, 300), RED).downscale_to_screen(screen, &options);
+        let small = create_test_image((60, 30), RED).downscale_to_screen(screen, &options);
+
+        assert_eq!(large.dimensions(), (120, 60));
+        assert_eq!(small.dimensions(), (60, 30));
+    }
+
+    #[test]
+    fn limit_resolution_applies_to_portrait_images_with_dimensions_swapped() {
+        let options = FrameOptions::default();
+
+        let landscape = create_test_image((600, 300), RED).limit_resolution((120, 80), &options);
+        let portrait = create_test_image((300, 600), RED).limit_resolution((120, 80), &options);
+        let small = create_test_image((60, 100), RED).limit_resolution((120, 80), &options);
+
+        assert_eq!(landscape.dimensions(), (120, 60));
+        assert_eq!(portrait.dimensions(), (60, 120));
+        assert_eq!(small.dimensions(), (60, 100));
+    }
+
+    #[test]
+    fn when_rotated_then_downscale_to_screen_fits_rotated_screen() {
+        let options = FrameOptions {
+            rotation: Rotation::D90,
+            ..FrameOptions::default()
+        };
+
+        let result = create_test_image((300, 600), RED).downscale_to_screen((120, 80), &options);
+
+        assert_eq!(result.dimensions(), (60, 120));
+    }
+
+    #[test]
+    fn compose_grid_places_tiles_row_by_row_and_leaves_missing_tiles_black() {
+        let tiles = [RED, GREEN, BLUE].map(|pixel| create_test_image((60, 40), pixel));
+
+        let result = compose_grid(&tiles, (120, 80), (2, 2));
+
+        assert_eq!(result.dimensions(), (120, 80));
+        assert_eq!(result.get_pixel(0, 0), RED);
+        assert_eq!(result.get_pixel(119, 0), GREEN);
+        assert_eq!(result.get_pixel(0, 79), BLUE);
+        assert_eq!(result.get_pixel(119, 79), Rgba([0, 0, 0, 255]));
+    }
+
+    #[test]
+    fn qr_code_is_placed_in_bottom_right_corner_of_rotated_screen() {
+        test_case(Rotation::D0, (90, 50));
+        test_case(Rotation::Auto, (90, 50));
+        test_case(Rotation::D90, (0, 50));
+        test_case(Rotation::D180, (0, 0));
+        test_case(Rotation::D270, (90, 0));
+
+        fn test_case(rotation: Rotation, expected_offset: (u32, u32)) {
+            let mut image = create_test_image((100, 60), RED);
+            let qr_code = create_test_image((10, 10), GREEN);
+
+            image.overlay_qr_code(&qr_code, rotation);
+
+            let (x, y) = expected_offset;
+            assert_eq!(image.get_pixel(x, y), GREEN, "{rotation:?}");
+            assert_eq!(image.get_pixel(x + 9, y + 9), GREEN, "{rotation:?}");
+            let green_pixels = image
+                .pixels()
+                .filter(|(_, _, pixel)| *pixel == GREEN)
+                .count();
+            assert_eq!(green_pixels, 100, "{rotation:?}");
+        }
+    }
+
+    #[test]
+    fn qr_code_is_rendered_at_least_at_given_size() {
+        let image = qr_code("https://nas.lan/photos/a.jpg", 100).unwrap();
+
+        assert!(image.width() >= 100 && image.width() == image.height());
+        assert_eq!(image.get_pixel(0, 0), Rgba([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn when_auto_rotation_then_image_is_rotated_only_if_orientation_does_not_match_screen() {
+        test_case((60, 40), (120, 80), Rotation::D0);
+        test_case((40, 60), (120, 80), Rotation::D90);
+        test_case((60, 40), (80, 120), Rotation::D90);
+        test_case((40, 60), (80, 120), Rotation::D0);
+        test_case((40, 40), (80, 120), Rotation::D0);
+        test_case((60, 40), (120, 120), Rotation::D0);
+
+        fn test_case(image: (u32, u32), screen: (u32, u32), expected: Rotation) {
+            assert_eq!(Rotation::Auto.resolve(image, screen), expected);
+        }
+    }
+
+    #[test]
+    fn when_auto_rotation_then_portrait_image_fills_landscape_screen() {
+        let pixel = Rgba([1, 2, 3, 255]);
+        let original = create_test_image((40, 60), pixel);
+        let screen = (120, 80);
+        let options = FrameOptions {
+            rotation: Rotation::Auto,
+            ..FrameOptions::default()
+        };
+
+        let result = internal_fit_to_screen_and_add_background(
+            &original,
+            screen,
+            &options,
+            panicking_brighten_and_blur_stub,
+        );
+
+        assert_eq!(result.dimensions(), screen);
+        assert!(result.pixels().all(|(_, _, p)| p == pixel));
+    }
+
+    #[test]
+    fn when_auto_rotated_then_background_is_derived_from_rotated_image() {
+        let mut original = create_test_image((30, 60), RED);
+        for y in 0..60 {
+            /* Once rotated clockwise, the left side is on top and the right side at the bottom */
+            for x in 0..10 {
+                original.put_pixel(x, y, GREEN);
+            }
+            for x in 20..30 {
+                original.put_pixel(x, y, BLUE);
+            }
+        }
+        let (x_res, y_res) = (120, 80); /* screen resolution */
+        let options = FrameOptions {
+            rotation: Rotation::Auto,
+            ..FrameOptions::default()
+        };
+        fn brighten_and_blur_stub(img: &DynamicImage) -> DynamicImage {
+            img.brighten(-55)
+        }
+
+        let result = internal_fit_to_screen_and_add_background(
+            &original,
+            (x_res, y_res),
+            &options,
+            brighten_and_blur_stub,
+        );
+
+        let expected_bg_h = 10;
+        for x in 0..x_res {
+            /* Foreground and background share the same orientation */
+            for y in 0..expected_bg_h {
+                assert_eq!(result.get_pixel(x, y), Rgba([0, 200, 0, 255]));
+            }
+            assert_eq!(result.get_pixel(x, expected_bg_h), GREEN);
+            assert_eq!(result.get_pixel(x, y_res / 2), RED);
+            assert_eq!(result.get_pixel(x, y_res - expected_bg_h - 1), BLUE);
+            for y in y_res - expected_bg_h..y_res {
+                assert_eq!(result.get_pixel(x, y), Rgba([0, 0, 200, 255]));
+            }
+        }
+    }
+
+    #[test]
+    fn when_no_upscale_then_smaller_image_is_centered_at_original_size_on_background() {
+        let original = create_test_image((30, 20), RED);
+        let (x_res, y_res) = (120, 80); /* screen resolution */
+        let options = FrameOptions {
+            no_upscale: true,
+            ..FrameOptions::default()
+        };
+        fn brighten_and_blur_stub(img: &DynamicImage) -> DynamicImage {
+            img.brighten(-55)
+        }
+
+        let result = internal_fit_to_screen_and_add_background(
+            &original,
+            (x_res, y_res),
+            &options,
+            brighten_and_blur_stub,
+        );
+
+        assert_eq!(result.dimensions(), (x_res, y_res));
+        let background = Rgba([200, 0, 0, 255]);
+        for (x, y, pixel) in result.pixels() {
+            let in_foreground = (45..75).contains(&x) && (30..50).contains(&y);
+            assert_eq!(pixel, if in_foreground { RED } else { background });
+        }
+    }
+
+    #[test]
+    fn when_no_upscale_then_bigger_image_is_still_downscaled() {
+        let original = create_test_image((240, 160), RED);
+        let options = FrameOptions {
+            no_upscale: true,
+            ..FrameOptions::default()
+        };
+
+        let result = internal_fit_to_screen_and_add_background(
+            &original,
+            (120, 80),
+            &options,
+            panicking_brighten_and_blur_stub,
+        );
+
+        assert_eq!(result.dimensions(), (120, 80));
+        assert!(result.pixels().all(|(_, _, p)| p == RED));
+    }
+
+    #[test]
+    fn when_sharpen_is_set_then_resized_foreground_is_sharpened() {
+        let mut original = create_test_image((240, 160), Rgba([64, 64, 64, 255]));
+        for x in 120..240 {
+            for y in 0..160 {
+                original.put_pixel(x, y, Rgba([192, 192, 192, 255]));
+            }
+        }
+        let screen = (120, 80);
+        let sharpen_options = FrameOptions {
+            sharpen: Some(1.0),
+            ..FrameOptions::default()
+        };
+
+        let plain = internal_fit_to_screen_and_add_background(
+            &original,
+            screen,
+            &FrameOptions::default(),
+            panicking_brighten_and_blur_stub,
+        );
+        let sharpened = internal_fit_to_screen_and_add_background(
+            &original,
+            screen,
+            &sharpen_options,
+            panicking_brighten_and_blur_stub,
+        );
+
+        /* Unsharp mask increases contrast next to the edge */
+        let (dark, bright) = (plain.get_pixel(58, 40)[0], plain.get_pixel(61, 40)[0]);
+        assert!(sharpened.get_pixel(58, 40)[0] < dark);
+        assert!(sharpened.get_pixel(61, 40)[0] > bright);
+    }
+
+    #[test]
+    fn when_limited_to_single_thread_then_result_is_the_same_as_with_multiple_threads() {
+        let mut original = create_test_image((60, 30), RED);
+        for x in 7..53 {
+            for y in 0..4 {
+                original.put_pixel(x, y, GREEN);
+            }
+        }
+        let screen = (120, 80);
+        fn brighten_and_blur_stub(img: &DynamicImage) -> DynamicImage {
+            img.brighten(-55)
+        }
+        let single_thread = FrameOptions {
+            threads: NonZeroUsize::MIN,
+            ..FrameOptions::default()
+        };
+        let three_threads = FrameOptions {
+            threads: NonZeroUsize::new(3).unwrap(),
+            ..FrameOptions::default()
+        };
+
+        let single_thread_result = internal_fit_to_screen_and_add_background(
+            &original,
+            screen,
+            &single_thread,
+            brighten_and_blur_stub,
+        );
+        let three_threads_result = internal_fit_to_screen_and_add_background(
+            &original,
+            screen,
+            &three_threads,
+            brighten_and_blur_stub,
+        );
+
+        assert_eq!(single_thread_result, three_threads_result);
+    }
+
+    #[cfg(feature = "jpeg-decoder")]
+    #[test]
+    fn cmyk_to_rgb_converts_inverted_cmyk_pixels() {
+        let cmyk = [255, 0, 0, 255, 0, 0, 0, 0, 255, 255, 255, 128];
+
+        let result = cmyk_to_rgb(&cmyk);
+
+        assert_eq!(result, [255, 0, 0, 0, 0, 0, 128, 128, 128]);
+    }
+
+    #[cfg(feature = "jpeg-decoder")]
+    #[test]
+    fn when_image_is_not_decodable_then_error_lists_both_decoders() {
+        let result = load_from_memory(&[0xFF, 0xD8, 0xFF, 0x00]);
+
+        let error = result.unwrap_err();
+        assert!(error.starts_with("image: "));
+        assert!(error.contains("; jpeg-decoder: "));
+    }
+
+    #[test]
+    fn pixel_bytes_are_in_texture_byte_order() {
+        let image = create_test_image((2, 1), Rgba([1, 2, 3, 255]));
+
+        assert_eq!(*pixel_bytes(&image, PixelFormat::Rgb24), [1, 2, 3, 1, 2, 3]);
+        assert_eq!(*pixel_bytes(&image, PixelFormat::Bgr24), [3, 2, 1, 3, 2, 1]);
+        assert_eq!(
+            *pixel_bytes(&image, PixelFormat::Rgba),
+            [1, 2, 3, 255, 1, 2, 3, 255]
+        );
+    }
+
+    #[test]
+    fn ordered_dither_with_1_bit_uses_only_min_and_max_values() {
+        let image = create_test_image((8, 8), Rgba([100, 128, 200, 255]));
+
+        let dithered = dither(image, Dither::Ordered, 1).into_rgb8();
+
+        assert!(dithered.iter().all(|&value| value == 0 || value == 255));
+        let white_count = |channel| dithered.pixels().filter(|p| p[channel] == 255).count();
+        assert!(white_count(0) < white_count(1));
+        assert!(white_count(1) < white_count(2));
+    }
+
+    #[test]
+    fn floyd_steinberg_dither_preserves_average_brightness() {
+        let image = create_test_image((16, 16), Rgba([128, 128, 128, 255]));
+
+        let dithered = dither(image, Dither::FloydSteinberg, 1).into_rgb8();
+
+        assert!(dithered.iter().all(|&value| value == 0 || value == 255));
+        let mean = dithered.iter().map(|&value| value as f32).sum::<f32>() / dithered.len() as f32;
+        assert!((mean - 128.0).abs() < 8.0, "mean {mean}");
+    }
+
+    #[test]
+    fn supported_formats_lists_enabled_decoders_only() {
+        let formats = supported_formats();
+
+        assert!(formats.iter().any(|format| format.starts_with("Jpeg: jpg")));
+        assert!(formats.iter().any(|format| format.starts_with("Png: png")));
+        assert!(!formats.iter().any(|format| format.starts_with("Gif")));
+    }
+
+    #[test]
+    fn matte_is_drawn_between_photo_and_background() {
+        let original = create_test_image((10, 10), RED);
+        let options = FrameOptions {
+            matte: Some((5, (0, 255, 0))),
+            ..FrameOptions::default()
+        };
+
+        let result =
+            internal_fit_to_screen_and_add_background(&original, (100, 50), &options, |img| {
+                img.brighten(-55)
+            });
+
+        /* The photo is fitted to 90x40, making it 40x40 at (30, 5) */
+        assert_eq!(result.dimensions(), (100, 50));
+        assert_eq!(result.get_pixel(10, 25), Rgba([200, 0, 0, 255]));
+        assert_eq!(result.get_pixel(27, 25), GREEN);
+        assert_eq!(result.get_pixel(50, 2), GREEN);
+        assert_eq!(result.get_pixel(50, 25), RED);
+        assert_eq!(result.get_pixel(72, 25), GREEN);
+    }
+
     fn create_test_image((w, h): (u32, u32), pixel: Rgba<u8>) -> DynamicImage {
         let mut image = DynamicImage::new_rgb8(w, h);
         for y in 0..h {
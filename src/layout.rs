@@ -0,0 +1,179 @@
+//! Aspect ratio preserving layout math used to frame photos on screen.
+//!
+//! Lets other tools (e.g. generating thumbnails or previews) reproduce the frame's layout
+
+/// Area of an image in pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+}
+
+/// Scales `src` dimensions preserving the aspect ratio to the largest size that fits within
+/// `bounds`
+pub fn fit_dimensions(src: (u32, u32), bounds: (u32, u32)) -> (u32, u32) {
+    let Dimensions { w, h } = Dimensions::from(src).resize(bounds.into());
+    (w.round() as u32, h.round() as u32)
+}
+
+/// Areas of an image of `src` dimensions that form the background on both sides of the image
+/// fitted to the `screen` (left and right, or top and bottom). Each area is scaled up to cover
+/// the screen space on its side
+pub fn background_crops(src: (u32, u32), screen: (u32, u32)) -> (Rect, Rect) {
+    let (crop1, crop2) = Dimensions::from(src).background_crops(screen.into());
+    (crop1.into(), crop2.into())
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Dimensions {
+    pub(crate) w: f64,
+    pub(crate) h: f64,
+}
+
+impl From<(u32, u32)> for Dimensions {
+    fn from((w, h): (u32, u32)) -> Self {
+        Self {
+            w: w as f64,
+            h: h as f64,
+        }
+    }
+}
+
+impl Dimensions {
+    pub(crate) const fn new(w: f64, h: f64) -> Self {
+        Self { w, h }
+    }
+
+    pub(crate) fn diff(self, Dimensions { w, h }: Dimensions) -> (f64, f64) {
+        (f64::abs(self.w - w), f64::abs(self.h - h))
+    }
+
+    pub(crate) fn is_exact_fit_to(self, target: Dimensions) -> bool {
+        let (w_diff, h_diff) = self.diff(target);
+        w_diff as u32 == 0 && h_diff as u32 == 0
+    }
+
+    pub(crate) fn aspect_ratio(self) -> f64 {
+        self.w / self.h
+    }
+
+    /// Relative difference between aspect ratios, e.g. 0.05 when they differ by 5%
+    pub(crate) fn aspect_ratio_difference(self, target: Dimensions) -> f64 {
+        let target_aspect_ratio = target.aspect_ratio();
+        f64::abs(self.aspect_ratio() - target_aspect_ratio) / target_aspect_ratio
+    }
+
+    /// Resize dimensions preserving aspect ratio. The dimensions are scaled to the maximum possible
+    /// size that fits within the bounds specified by `new_width` and `new_height`.
+    pub(crate) fn resize(
+        self,
+        Dimensions {
+            w: new_width,
+            h: new_height,
+        }: Dimensions,
+    ) -> Dimensions {
+        let w_ratio = new_width / self.w;
+        let h_ratio = new_height / self.h;
+
+        let ratio = f64::min(w_ratio, h_ratio);
+
+        let nw = f64::max(self.w * ratio, 1.0);
+        let nh = f64::max(self.h * ratio, 1.0);
+
+        Dimensions::new(nw, nh)
+    }
+
+    /// Calculates coordinates of parts of the foreground that will form the background fills.
+    pub(crate) fn background_crops(self, screen_size: Dimensions) -> (Coords, Coords) {
+        let screen_to_image_projection = screen_size.resize(self);
+        let (w_diff, h_diff) = screen_to_image_projection.diff(self);
+        let (bg_x, bg_y) = (w_diff / 2.0, h_diff / 2.0);
+
+        let image_to_projected_screen = self.resize(screen_to_image_projection);
+        let (w_diff, h_diff) = image_to_projected_screen.diff(screen_to_image_projection);
+
+        let Dimensions {
+            w: screen_w,
+            h: screen_h,
+        } = screen_to_image_projection;
+
+        if w_diff > 0.0 {
+            /* Needs background on left and right. */
+            let bg_w = w_diff / 2.0;
+            (
+                Coords {
+                    x: bg_x,
+                    y: bg_y,
+                    w: bg_w,
+                    h: screen_h,
+                },
+                Coords {
+                    x: self.w - bg_w,
+                    y: bg_y,
+                    w: bg_w,
+                    h: screen_h,
+                },
+            )
+        } else {
+            /* Needs background on top and bottom .*/
+            let bg_h = h_diff / 2.0;
+            (
+                Coords {
+                    x: bg_x,
+                    y: bg_y,
+                    w: screen_w,
+                    h: bg_h,
+                },
+                Coords {
+                    x: bg_x,
+                    y: self.h - bg_h,
+                    w: screen_w,
+                    h: bg_h,
+                },
+            )
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Coords {
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+}
+
+impl From<Coords> for Rect {
+    /// Rounds outward to whole pixels
+    fn from(Coords { x, y, w, h }: Coords) -> Self {
+        Rect {
+            x: x.floor() as u32,
+            y: y.floor() as u32,
+            w: w.ceil() as u32,
+            h: h.ceil() as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_dimensions_preserves_aspect_ratio_within_bounds() {
+        assert_eq!(fit_dimensions((600, 300), (120, 80)), (120, 60));
+        assert_eq!(fit_dimensions((30, 60), (120, 80)), (40, 80));
+        assert_eq!(fit_dimensions((60, 40), (120, 80)), (120, 80));
+    }
+
+    #[test]
+    fn background_crops_are_taken_from_edges_of_the_image() {
+        let (left, right) = background_crops((50, 80), (120, 80));
+
+        let (y, w, h) = (23, 15, 34);
+        assert_eq!(left, Rect { x: 0, y, w, h });
+        assert_eq!(right, Rect { x: 35, y, w, h });
+    }
+}
@@ -1,10 +1,100 @@
 //! Logging
 
 use core::fmt::Debug;
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::Mutex,
+    time::SystemTime,
+};
 
-use log::Level;
+use chrono::{DateTime, Utc};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use simple_logger::SimpleLogger;
 
-use crate::http::{Client, Response};
+use crate::{
+    cli::LogLevel,
+    error::ErrorToString,
+    http::{Client, Response},
+};
+
+/// Sets up logging to stderr, or appending to `log_file` when set
+pub fn init(level: LogLevel, log_file: Option<&Path>) -> Result<(), String> {
+    let level = LevelFilter::from(level);
+    match log_file {
+        None => SimpleLogger::new()
+            .with_level(level)
+            .init()
+            .map_err_to_string(),
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|error| format!("{}: {error}", path.to_string_lossy()))?;
+            log::set_boxed_logger(Box::new(FileLogger {
+                file: Mutex::new(file),
+                level,
+            }))
+            .map_err_to_string()?;
+            log::set_max_level(level);
+            Ok(())
+        }
+    }
+}
+
+/// Appends log records to a file
+struct FileLogger {
+    file: Mutex<File>,
+    level: LevelFilter,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format_record(record, SystemTime::now());
+        if let Ok(mut file) = self.file.lock() {
+            /* There is nowhere left to report a failure to */
+            let _ = file.write_all(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut file) = self.file.lock() {
+            let _ = file.flush();
+        }
+    }
+}
+
+fn format_record(record: &Record, time: SystemTime) -> String {
+    format!(
+        "{} {:<5} [{}] {}\n",
+        DateTime::<Utc>::from(time).format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+        record.level(),
+        record.target(),
+        record.args()
+    )
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(value: LogLevel) -> Self {
+        match value {
+            LogLevel::Off => LevelFilter::Off,
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
 
 /// Adds logging to [Client]
 #[derive(Clone, Debug)]
@@ -61,3 +151,29 @@ where
         response
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn format_record_prefixes_message_with_utc_time_level_and_target() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+
+        let line = format_record(
+            &Record::builder()
+                .args(format_args!("Displaying photo.jpg"))
+                .level(Level::Info)
+                .target("syno_photo_frame")
+                .build(),
+            time,
+        );
+
+        assert_eq!(
+            line,
+            "2023-11-14T22:13:20.123Z INFO  [syno_photo_frame] Displaying photo.jpg\n"
+        );
+    }
+}
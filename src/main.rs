@@ -1,24 +1,27 @@
 use std::error::Error;
 
-use log::LevelFilter;
 use rand::{self, seq::SliceRandom, Rng};
-use simple_logger::SimpleLogger;
 
 use syno_photo_frame::{
     self,
     cli::{Cli, Parser},
     error::FrameError,
+    logging,
     sdl::{self, SdlWrapper},
     FrameResult, Random,
 };
 
 fn main() -> Result<(), Box<dyn Error>> {
-    SimpleLogger::new()
-        .with_level(LevelFilter::Debug)
-        .env()
-        .init()?;
+    let cli = Cli::parse();
+    if cli.formats {
+        syno_photo_frame::supported_formats()
+            .iter()
+            .for_each(|format| println!("{format}"));
+        return Ok(());
+    }
+    logging::init(cli.log_level, cli.log_file.as_deref())?;
 
-    match init_and_run() {
+    match init_and_run(cli) {
         Err(FrameError::Other(error)) => {
             log::error!("{error}");
             Err(error)?
@@ -27,20 +30,24 @@ fn main() -> Result<(), Box<dyn Error>> {
     }
 }
 
-fn init_and_run() -> FrameResult<()> {
-    let cli = Cli::parse();
+fn init_and_run(mut cli: Cli) -> FrameResult<()> {
+    cli.apply_netrc()?;
+    cli.apply_timelapse();
+    if cli.check {
+        syno_photo_frame::check(&cli)?;
+        return Ok(());
+    }
 
     /* SDL */
     let video = sdl::init_video()?;
     let display_size = sdl::display_size(&video)?;
-    let canvas = sdl::create_canvas(&video, display_size)?;
+    sdl::set_render_hints(cli.render_driver.as_deref(), cli.scale_quality);
+    let canvas = sdl::create_canvas(&video, display_size, !cli.no_vsync, cli.canvas_color)?;
     let texture_creator = canvas.texture_creator();
-    let textures = [
-        sdl::create_texture(&texture_creator, display_size)?,
-        sdl::create_texture(&texture_creator, display_size)?,
-    ];
     let events = video.sdl().event_pump()?;
-    let mut sdl = SdlWrapper::new(canvas, textures, events);
+    let mut sdl = SdlWrapper::new(canvas, &texture_creator, cli.pixel_format, events)?
+        .with_max_fps(cli.max_fps)
+        .with_pixel_shift(cli.pixel_shift, cli.canvas_color.into());
 
     /* Random */
     let random: Random = (
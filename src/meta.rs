@@ -0,0 +1,216 @@
+//! Photo metadata
+
+use std::{
+    fmt::{Display, Formatter},
+    io::Cursor,
+    time::Duration,
+};
+
+use chrono::NaiveDateTime;
+use exif::{Exif, In, Reader, Tag, Value};
+
+/// Longer display durations set for a photo are shortened to this
+const MAX_DISPLAY_DURATION_SECONDS: u64 = 24 * 60 * 60;
+
+/// Information about a photo that can be presented alongside it. Fields missing from the EXIF
+/// data are [None]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PhotoMeta {
+    pub file_name: String,
+    /// Date and time when the photo was taken
+    pub taken_at: Option<NaiveDateTime>,
+    /// Camera make and model
+    pub camera: Option<String>,
+    /// GPS coordinates in degrees (latitude, longitude)
+    pub location: Option<(f64, f64)>,
+    /// Display time overriding the photo change interval, set as `duration=<seconds>` in the
+    /// EXIF user comment or in a sidecar file
+    pub display_duration: Option<Duration>,
+    /// Index of the photo in the album listing and number of photos left in the display
+    /// sequence, see [crate::slideshow::PhotoFile]
+    pub sequence_position: Option<(u32, usize)>,
+}
+
+impl PhotoMeta {
+    /// Reads metadata from EXIF data embedded in the photo file
+    pub fn read(file_name: String, bytes: &[u8]) -> PhotoMeta {
+        match Reader::new().read_from_container(&mut Cursor::new(bytes)) {
+            Ok(exif) => PhotoMeta {
+                file_name,
+                taken_at: taken_at(&exif),
+                camera: camera(&exif),
+                location: location(&exif),
+                display_duration: user_comment(&exif)
+                    .and_then(|comment| parse_display_duration_comment(&comment)),
+                sequence_position: None,
+            },
+            Err(error) => {
+                log::debug!("{file_name}: no EXIF data: {error}");
+                PhotoMeta {
+                    file_name,
+                    ..PhotoMeta::default()
+                }
+            }
+        }
+    }
+}
+
+fn taken_at(exif: &Exif) -> Option<NaiveDateTime> {
+    [Tag::DateTimeOriginal, Tag::DateTime]
+        .into_iter()
+        .filter_map(|tag| ascii(exif, tag))
+        .find_map(|date_time| NaiveDateTime::parse_from_str(&date_time, "%Y:%m:%d %H:%M:%S").ok())
+}
+
+fn camera(exif: &Exif) -> Option<String> {
+    match (ascii(exif, Tag::Make), ascii(exif, Tag::Model)) {
+        /* Model often repeats the make already, e.g. "Canon" "Canon EOS 80D" */
+        (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+        (Some(make), Some(model)) => Some(format!("{make} {model}")),
+        (make, model) => make.or(model),
+    }
+}
+
+fn location(exif: &Exif) -> Option<(f64, f64)> {
+    let coordinate = |tag, ref_tag, negative_ref| {
+        let degrees = match &exif.get_field(tag, In::PRIMARY)?.value {
+            Value::Rational(dms) if dms.len() == 3 => {
+                dms_to_degrees(dms[0].to_f64(), dms[1].to_f64(), dms[2].to_f64())
+            }
+            _ => return None,
+        };
+        let is_negative = ascii(exif, ref_tag).is_some_and(|r| r == negative_ref);
+        Some(if is_negative { -degrees } else { degrees })
+    };
+    Some((
+        coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, "S")?,
+        coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, "W")?,
+    ))
+}
+
+/// Decodes the user comment, skipping its 8-byte character code prefix. Only ASCII (or
+/// undefined) character codes are supported
+fn user_comment(exif: &Exif) -> Option<String> {
+    match &exif.get_field(Tag::UserComment, In::PRIMARY)?.value {
+        Value::Undefined(bytes, _) if bytes.len() > 8 && !bytes.starts_with(b"UNICODE") => Some(
+            String::from_utf8_lossy(&bytes[8..])
+                .trim_end_matches('\0')
+                .to_string(),
+        ),
+        _ => None,
+    }
+}
+
+/// Finds a `duration=<seconds>` token in a comment
+fn parse_display_duration_comment(comment: &str) -> Option<Duration> {
+    comment
+        .split(|c: char| c.is_whitespace() || c == ';' || c == ',')
+        .find_map(|token| token.strip_prefix("duration="))
+        .and_then(parse_display_duration)
+}
+
+/// Parses a positive number of seconds, up to [MAX_DISPLAY_DURATION_SECONDS]
+pub fn parse_display_duration(seconds: &str) -> Option<Duration> {
+    seconds
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|&seconds| seconds > 0)
+        .map(|seconds| Duration::from_secs(seconds.min(MAX_DISPLAY_DURATION_SECONDS)))
+}
+
+fn dms_to_degrees(degrees: f64, minutes: f64, seconds: f64) -> f64 {
+    degrees + minutes / 60.0 + seconds / 3600.0
+}
+
+fn ascii(exif: &Exif, tag: Tag) -> Option<String> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values
+            .first()
+            .map(|value| String::from_utf8_lossy(value).trim().to_string())
+            .filter(|value| !value.is_empty()),
+        _ => None,
+    }
+}
+
+impl Display for PhotoMeta {
+    /// Formats available fields, separated by commas
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file_name)?;
+        if let Some(taken_at) = self.taken_at {
+            write!(f, ", {taken_at}")?;
+        }
+        if let Some(camera) = &self.camera {
+            write!(f, ", {camera}")?;
+        }
+        if let Some((latitude, longitude)) = self.location {
+            write!(f, ", {latitude:.5}, {longitude:.5}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn when_file_has_no_exif_then_only_file_name_is_set() {
+        let result = PhotoMeta::read("photo.jpg".to_string(), &[1, 2, 3]);
+
+        assert_eq!(
+            result,
+            PhotoMeta {
+                file_name: "photo.jpg".to_string(),
+                ..PhotoMeta::default()
+            }
+        );
+    }
+
+    #[test]
+    fn dms_to_degrees_converts_minutes_and_seconds() {
+        let result = dms_to_degrees(52.0, 13.0, 48.0);
+
+        assert!((result - 52.23).abs() < 1e-9);
+    }
+
+    #[test]
+    fn parse_display_duration_comment_finds_duration_token() {
+        assert_eq!(
+            parse_display_duration_comment("Sunset; duration=45"),
+            Some(Duration::from_secs(45))
+        );
+        assert_eq!(parse_display_duration_comment("duration=0"), None);
+        assert_eq!(parse_display_duration_comment("duration=long"), None);
+        assert_eq!(parse_display_duration_comment("Sunset"), None);
+    }
+
+    #[test]
+    fn parse_display_duration_caps_long_durations() {
+        assert_eq!(
+            parse_display_duration("18446744073709551615"),
+            Some(Duration::from_secs(MAX_DISPLAY_DURATION_SECONDS))
+        );
+    }
+
+    #[test]
+    fn display_omits_missing_fields() {
+        let mut meta = PhotoMeta {
+            file_name: "photo.jpg".to_string(),
+            ..PhotoMeta::default()
+        };
+        assert_eq!(meta.to_string(), "photo.jpg");
+
+        meta.camera = Some("Canon EOS 80D".to_string());
+        assert_eq!(meta.to_string(), "photo.jpg, Canon EOS 80D");
+
+        meta.taken_at = NaiveDate::from_ymd_opt(2020, 5, 4).and_then(|d| d.and_hms_opt(3, 2, 1));
+        meta.location = Some((52.23, -21.0));
+        assert_eq!(
+            meta.to_string(),
+            "photo.jpg, 2020-05-04 03:02:01, Canon EOS 80D, 52.23000, -21.00000"
+        );
+    }
+}
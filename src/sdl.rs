@@ -1,16 +1,43 @@
 //! Rendering
 
-pub(crate) use sdl2::pixels::Color;
+pub use sdl2::pixels::Color;
+
+use std::{
+    collections::VecDeque,
+    thread,
+    time::{Duration, Instant},
+};
 
 use sdl2::{
     event::Event,
+    hint,
+    keyboard::Keycode,
     pixels::PixelFormatEnum,
+    rect::Rect,
     render::{BlendMode, Canvas, Texture, TextureCreator},
     video::{DisplayMode, Window, WindowContext},
     EventPump, VideoSubsystem,
 };
 
-use crate::{error::ErrorToString, QuitEvent};
+use crate::{
+    cli::{PixelFormat, ScaleQuality},
+    error::ErrorToString,
+    QuitEvent,
+};
+
+/// How long the image stays at each offset of [PIXEL_SHIFT_PATTERN]
+const PIXEL_SHIFT_INTERVAL: Duration = Duration::from_secs(3 * 60);
+/// Offsets (in pixels) the image cycles through with [SdlWrapper::with_pixel_shift]
+const PIXEL_SHIFT_PATTERN: [(i32, i32); 8] = [
+    (0, 0),
+    (2, 0),
+    (2, 2),
+    (0, 2),
+    (-2, 2),
+    (-2, 0),
+    (-2, -2),
+    (0, -2),
+];
 
 /// Isolates [sdl2::Sdl] context for testing
 #[cfg_attr(test, mockall::automock)]
@@ -24,7 +51,28 @@ pub trait Sdl {
     fn swap_textures(&mut self);
     fn fill_canvas(&mut self, color: Color) -> Result<(), String>;
     fn present_canvas(&mut self);
+    /// Polls pending events. Control events are queued to be taken by
+    /// [Sdl::take_control_event]
     fn handle_quit_event(&mut self) -> Result<(), QuitEvent>;
+    /// Returns the oldest queued control event
+    fn take_control_event(&mut self) -> Option<ControlEvent>;
+    /// Queries the display size again, e.g. after a TV renegotiated the HDMI resolution. When it
+    /// changed, resizes the window and recreates both textures, with their content lost. Returns
+    /// true when the size changed
+    fn refresh_size(&mut self) -> Result<bool, String>;
+}
+
+/// Slideshow control requested by the user (e.g. with a keyboard or a GPIO button mapped to a
+/// key)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlEvent {
+    /// Show the next photo without waiting for the interval to pass (Right arrow, Space or
+    /// Enter)
+    Next,
+    /// Show the previously displayed photo again (Left arrow or Backspace)
+    Previous,
+    /// Never show the displayed photo again, see --blacklist (Delete)
+    Blacklist,
 }
 
 /// Index of a texture to operate on (used mainly by transition effects)
@@ -52,8 +100,17 @@ impl Sdl for SdlWrapper<'_> {
     }
 
     fn copy_texture_to_canvas(&mut self, index: TextureIndex) -> Result<(), String> {
-        self.canvas
-            .copy(&self.textures[self.texture_index(index)], None, None)
+        let texture = &self.textures[self.texture_index(index)];
+        let Some((background, started)) = self.pixel_shift else {
+            return self.canvas.copy(texture, None, None);
+        };
+        let (w, h) = self.size;
+        let (x, y) = pixel_shift_offset(started.elapsed());
+        /* Edges the shifted texture leaves uncovered are redrawn opaque, so that they don't affect
+         * blending of the texture itself */
+        self.canvas.set_draw_color(background);
+        self.canvas.fill_rects(&exposed_edges((w, h), (x, y)))?;
+        self.canvas.copy(texture, None, Rect::new(x, y, w, h))
     }
 
     fn swap_textures(&mut self) {
@@ -66,49 +123,173 @@ impl Sdl for SdlWrapper<'_> {
     }
 
     fn present_canvas(&mut self) {
-        self.canvas.present()
+        if let (Some(min_frame_duration), Some(last_present)) =
+            (self.min_frame_duration, self.last_present)
+        {
+            thread::sleep(min_frame_duration.saturating_sub(last_present.elapsed()));
+        }
+        self.canvas.present();
+        self.last_present = Some(Instant::now());
     }
 
     fn handle_quit_event(&mut self) -> Result<(), QuitEvent> {
-        let exit_requested = self.events.poll_iter().any(|e| match e {
-            event @ (Event::Quit { .. } | Event::AppTerminating { .. }) => {
-                log::debug!("SDL event received: {event:?}");
-                true
+        let mut exit_requested = false;
+        for event in self.events.poll_iter() {
+            match event {
+                Event::Quit { .. } | Event::AppTerminating { .. } => {
+                    log::debug!("SDL event received: {event:?}");
+                    exit_requested = true;
+                }
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(control_event) = control_event(keycode) {
+                        log::debug!("Control event received: {control_event:?}");
+                        self.control_events.push_back(control_event);
+                    }
+                }
+                _ => (),
             }
-            _ => false,
-        });
+        }
         if exit_requested {
             Err(QuitEvent)
         } else {
             Ok(())
         }
     }
+
+    fn take_control_event(&mut self) -> Option<ControlEvent> {
+        self.control_events.pop_front()
+    }
+
+    fn refresh_size(&mut self) -> Result<bool, String> {
+        let size = display_size(self.canvas.window().subsystem())?;
+        if size == self.size {
+            return Ok(false);
+        }
+        log::info!("Display size changed from {:?} to {size:?}", self.size);
+        let (w, h) = size;
+        self.canvas
+            .window_mut()
+            .set_size(w, h)
+            .map_err_to_string()?;
+        self.textures = create_textures(self.texture_creator, size, self.pixel_format)?;
+        self.pitch = pitch(size, &self.textures[0]);
+        self.size = size;
+        Ok(true)
+    }
+}
+
+fn create_textures<'a>(
+    texture_creator: &'a TextureCreator<WindowContext>,
+    size: (u32, u32),
+    pixel_format: PixelFormat,
+) -> Result<[Texture<'a>; 2], String> {
+    Ok([
+        create_texture(texture_creator, size, pixel_format)?,
+        create_texture(texture_creator, size, pixel_format)?,
+    ])
+}
+
+/// Returns the offset of [PIXEL_SHIFT_PATTERN] for the time elapsed since the pattern started
+fn pixel_shift_offset(elapsed: Duration) -> (i32, i32) {
+    let step = elapsed.as_secs() / PIXEL_SHIFT_INTERVAL.as_secs();
+    PIXEL_SHIFT_PATTERN[(step % PIXEL_SHIFT_PATTERN.len() as u64) as usize]
+}
+
+/// Strips of the screen not covered by an image of the screen size moved by `(x, y)`
+fn exposed_edges((w, h): (u32, u32), (x, y): (i32, i32)) -> Vec<Rect> {
+    let (w_signed, h_signed) = (w as i32, h as i32);
+    let mut edges = Vec::with_capacity(2);
+    match x.signum() {
+        1 => edges.push(Rect::new(0, 0, x.unsigned_abs(), h)),
+        -1 => edges.push(Rect::new(w_signed + x, 0, x.unsigned_abs(), h)),
+        _ => (),
+    }
+    match y.signum() {
+        1 => edges.push(Rect::new(0, 0, w, y.unsigned_abs())),
+        -1 => edges.push(Rect::new(0, h_signed + y, w, y.unsigned_abs())),
+        _ => (),
+    }
+    edges
+}
+
+/// Number of bytes in a row of pixel data of `texture`, `w` pixels wide
+fn pitch((w, _): (u32, u32), texture: &Texture) -> usize {
+    w as usize * texture.query().format.byte_size_per_pixel()
+}
+
+fn control_event(keycode: Keycode) -> Option<ControlEvent> {
+    match keycode {
+        Keycode::Right | Keycode::Space | Keycode::Return => Some(ControlEvent::Next),
+        Keycode::Left | Keycode::Backspace => Some(ControlEvent::Previous),
+        Keycode::Delete => Some(ControlEvent::Blacklist),
+        _ => None,
+    }
 }
 
 /// Container for components from [sdl2::Sdl]
 pub struct SdlWrapper<'a> {
     canvas: Canvas<Window>,
+    texture_creator: &'a TextureCreator<WindowContext>,
+    pixel_format: PixelFormat,
     textures: [Texture<'a>; 2],
     current_texture: usize,
     events: EventPump,
+    control_events: VecDeque<ControlEvent>,
     size: (u32, u32),
     /// Number of bytes in a row of pixel data, in other words image width multiplied by bytes-per-pixel
     pitch: usize,
+    /// Shortest time between presenting two frames, set with [SdlWrapper::with_max_fps]
+    min_frame_duration: Option<Duration>,
+    last_present: Option<Instant>,
+    /// Color of edges exposed by shifting the image and start of the shift pattern, set with
+    /// [SdlWrapper::with_pixel_shift]
+    pixel_shift: Option<(Color, Instant)>,
 }
 
 impl<'a> SdlWrapper<'a> {
-    pub fn new(canvas: Canvas<Window>, textures: [Texture<'a>; 2], events: EventPump) -> Self {
+    /// Wraps `canvas` with two textures of the window size in `pixel_format`, created with
+    /// `texture_creator`
+    pub fn new(
+        canvas: Canvas<Window>,
+        texture_creator: &'a TextureCreator<WindowContext>,
+        pixel_format: PixelFormat,
+        events: EventPump,
+    ) -> Result<Self, String> {
         let size = canvas.window().size();
-        let (w, ..) = size;
-        const BYTE_SIZE_PER_PIXEL: usize = 3;
-        SdlWrapper {
+        let textures = create_textures(texture_creator, size, pixel_format)?;
+        Ok(SdlWrapper {
+            pitch: pitch(size, &textures[0]),
             canvas,
+            texture_creator,
+            pixel_format,
             textures,
             current_texture: 0,
             events,
+            control_events: VecDeque::new(),
             size,
-            pitch: w as usize * BYTE_SIZE_PER_PIXEL,
-        }
+            min_frame_duration: None,
+            last_present: None,
+            pixel_shift: None,
+        })
+    }
+
+    /// Sleeps before presenting the canvas as needed to present at most `max_fps` frames per
+    /// second, regardless of vsync
+    pub fn with_max_fps(mut self, max_fps: Option<u32>) -> Self {
+        self.min_frame_duration = max_fps.map(|fps| Duration::from_secs(1) / fps);
+        self
+    }
+
+    /// Shifts the image by a few pixels every [PIXEL_SHIFT_INTERVAL] to prevent burn-in of OLED
+    /// panels, filling the exposed edges with `background`. A new offset applies from the next
+    /// redraw
+    pub fn with_pixel_shift(mut self, pixel_shift: bool, background: Color) -> Self {
+        self.pixel_shift = pixel_shift.then(|| (background, Instant::now()));
+        self
     }
 
     fn texture_index(&self, index: TextureIndex) -> usize {
@@ -132,8 +313,33 @@ pub fn display_size(video: &VideoSubsystem) -> Result<(u32, u32), String> {
     Ok((u32::try_from(w).unwrap(), u32::try_from(h).unwrap()))
 }
 
-/// Sets up a renderer
-pub fn create_canvas(video: &VideoSubsystem, (w, h): (u32, u32)) -> Result<Canvas<Window>, String> {
+/// Sets SDL hints selecting the render driver and texture filtering. Needs to be called before
+/// [create_canvas]. SDL defaults are kept for options that are None
+pub fn set_render_hints(render_driver: Option<&str>, scale_quality: Option<ScaleQuality>) {
+    let hints = [
+        ("SDL_RENDER_DRIVER", render_driver),
+        (
+            "SDL_RENDER_SCALE_QUALITY",
+            scale_quality.map(ScaleQuality::hint_value),
+        ),
+    ];
+    for (name, value) in hints {
+        if let Some(value) = value {
+            if !hint::set(name, value) {
+                log::warn!("Setting {name} to {value} failed");
+            }
+        }
+    }
+}
+
+/// Sets up a renderer, cleared to `color`. Presenting the canvas is synchronized with the display
+/// refresh rate when `vsync` is true
+pub fn create_canvas(
+    video: &VideoSubsystem,
+    (w, h): (u32, u32),
+    vsync: bool,
+    color: (u8, u8, u8),
+) -> Result<Canvas<Window>, String> {
     let window = video
         .window("syno-photo-frame", w, h)
         .borderless()
@@ -141,13 +347,16 @@ pub fn create_canvas(video: &VideoSubsystem, (w, h): (u32, u32)) -> Result<Canva
         .map_err_to_string()?;
     /* Seems this needs to be set _after_ window has been created. */
     video.sdl().mouse().show_cursor(false);
-    let mut canvas = window
-        .into_canvas()
-        .present_vsync()
-        .build()
-        .map_err_to_string()?;
+    let mut canvas_builder = window.into_canvas();
+    if vsync {
+        canvas_builder = canvas_builder.present_vsync();
+    }
+    let mut canvas = canvas_builder.build().map_err_to_string()?;
     /* Transition effects draw semi-transparent box on canvas */
     canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(color);
+    canvas.clear();
+    canvas.present();
     Ok(canvas)
 }
 
@@ -155,10 +364,59 @@ pub fn create_canvas(video: &VideoSubsystem, (w, h): (u32, u32)) -> Result<Canva
 pub fn create_texture(
     texture_creator: &TextureCreator<WindowContext>,
     (w, h): (u32, u32),
+    pixel_format: PixelFormat,
 ) -> Result<Texture, String> {
     let mut texture = texture_creator
-        .create_texture_static(PixelFormatEnum::RGB24, w, h)
+        .create_texture_static(PixelFormatEnum::from(pixel_format), w, h)
         .map_err_to_string()?;
     texture.set_blend_mode(BlendMode::Blend);
     Ok(texture)
 }
+
+impl ScaleQuality {
+    const fn hint_value(self) -> &'static str {
+        match self {
+            ScaleQuality::Nearest => "nearest",
+            ScaleQuality::Linear => "linear",
+            ScaleQuality::Best => "best",
+        }
+    }
+}
+
+impl From<PixelFormat> for PixelFormatEnum {
+    fn from(value: PixelFormat) -> Self {
+        match value {
+            PixelFormat::Rgb24 => PixelFormatEnum::RGB24,
+            PixelFormat::Bgr24 => PixelFormatEnum::BGR24,
+            /* Byte order RGBA regardless of endianness, unlike RGBA8888 */
+            PixelFormat::Rgba => PixelFormatEnum::RGBA32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixel_shift_offset_cycles_through_pattern() {
+        let offsets: Vec<_> = [0, 179, 180, 3 * 180, 8 * 180 + 1]
+            .map(|secs| pixel_shift_offset(Duration::from_secs(secs)))
+            .to_vec();
+
+        assert_eq!(offsets, [(0, 0), (0, 0), (2, 0), (0, 2), (0, 0)]);
+    }
+
+    #[test]
+    fn exposed_edges_are_on_the_sides_the_image_moved_away_from() {
+        assert!(exposed_edges((100, 50), (0, 0)).is_empty());
+        assert_eq!(
+            exposed_edges((100, 50), (2, -2)),
+            [Rect::new(0, 0, 2, 50), Rect::new(0, 48, 100, 2)]
+        );
+        assert_eq!(
+            exposed_edges((100, 50), (-2, 2)),
+            [Rect::new(98, 0, 2, 50), Rect::new(0, 0, 100, 2)]
+        );
+    }
+}
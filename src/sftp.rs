@@ -0,0 +1,238 @@
+//! SFTP request-response handling
+
+use std::{
+    cell::Cell,
+    env,
+    io::Read,
+    net::{SocketAddr, TcpStream},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use ssh2::{CheckResult, KnownHostFileKind, Session, Sftp};
+
+use crate::{
+    error::ErrorToString,
+    http::Url,
+    source::{self, PhotoSource},
+};
+
+/// Used when the server URL does not specify a port
+const SSH_PORT: u16 = 22;
+
+/// Host keys the server's key is checked against, relative to the home folder
+const KNOWN_HOSTS_FILE: &str = ".ssh/known_hosts";
+
+/// Used unless set with [Client::with_timeout]
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// SFTP server connection details. Each request is sent over a new SSH session.
+///
+/// The server's host key is checked against `~/.ssh/known_hosts` before logging in. A key that
+/// does not match the known one fails the connection. A server missing from the file is only
+/// warned about, as there is no way to confirm its key interactively
+pub struct Client {
+    server: Url,
+    user: String,
+    password: Option<String>,
+    identity_file: Option<PathBuf>,
+    timeout: Duration,
+    /// Set once the warning about an unverified host key has been logged, so that it is not
+    /// repeated for every request
+    unverified_host_key_logged: Cell<bool>,
+}
+
+impl Client {
+    pub fn new(server: &Url, user: &Option<String>, password: &Option<String>) -> Client {
+        Client {
+            server: server.clone(),
+            user: user.clone().unwrap_or_default(),
+            password: password.clone(),
+            identity_file: None,
+            timeout: DEFAULT_TIMEOUT,
+            unverified_host_key_logged: Cell::new(false),
+        }
+    }
+
+    /// Authenticates with a private key instead of a password. The password, when set, is used
+    /// as the key passphrase
+    pub fn with_identity_file(mut self, identity_file: Option<PathBuf>) -> Self {
+        self.identity_file = identity_file;
+        self
+    }
+
+    /// Sets the time to wait for the server to connect and to answer each request
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn with_session<T>(
+        &self,
+        request: impl FnOnce(&Sftp) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let session = self.connect()?;
+        let sftp = session.sftp().map_err_to_string()?;
+        let result = request(&sftp);
+        if let Err(error) = session.disconnect(None, "", None) {
+            log::debug!("SSH disconnect: {error}");
+        }
+        result
+    }
+
+    fn connect(&self) -> Result<Session, String> {
+        if self.user.is_empty() {
+            return Err("SFTP requires --user".to_string());
+        }
+        let addrs = source::socket_addrs(&self.server, SSH_PORT)?;
+        let stream = connect_any(&addrs, self.timeout)?;
+        let mut session = Session::new().map_err_to_string()?;
+        session.set_timeout(self.timeout.as_millis().try_into().unwrap_or(u32::MAX));
+        session.set_tcp_stream(stream);
+        session.handshake().map_err_to_string()?;
+        /* Before authenticating, so that credentials are not sent to an impersonated server */
+        self.verify_host_key(&session)?;
+        match &self.identity_file {
+            Some(identity_file) => session.userauth_pubkey_file(
+                &self.user,
+                None,
+                identity_file,
+                self.password.as_deref(),
+            ),
+            None => session.userauth_password(&self.user, self.password.as_deref().unwrap_or("")),
+        }
+        .map_err_to_string()?;
+        Ok(session)
+    }
+
+    /// Checks the host key presented by the server against [KNOWN_HOSTS_FILE]
+    fn verify_host_key(&self, session: &Session) -> Result<(), String> {
+        let host = self.server.host_str().unwrap_or_default();
+        let port = self.server.port().unwrap_or(SSH_PORT);
+        let (key, _) = session
+            .host_key()
+            .ok_or_else(|| format!("{host} did not present a host key"))?;
+        let Some(known_hosts_path) =
+            env::var_os("HOME").map(|home| PathBuf::from(home).join(KNOWN_HOSTS_FILE))
+        else {
+            self.warn_unverified_host_key(&format!(
+                "{KNOWN_HOSTS_FILE} not found, as HOME is not set"
+            ));
+            return Ok(());
+        };
+        let known_hosts_name = known_hosts_path.to_string_lossy();
+        let mut known_hosts = session.known_hosts().map_err_to_string()?;
+        if let Err(error) = known_hosts.read_file(&known_hosts_path, KnownHostFileKind::OpenSSH) {
+            self.warn_unverified_host_key(&format!("{known_hosts_name}: {error}"));
+            return Ok(());
+        }
+        match known_hosts.check_port(host, port, key) {
+            CheckResult::Match => Ok(()),
+            CheckResult::NotFound => {
+                self.warn_unverified_host_key(&format!(
+                    "{host} is not in {known_hosts_name}. Add it with `ssh-keyscan {host} >> \
+                    {known_hosts_name}`"
+                ));
+                Ok(())
+            }
+            CheckResult::Mismatch => Err(format!(
+                "Host key of {host} does not match the one in {known_hosts_name}. Not logging \
+                in, as the server may be impersonated"
+            )),
+            CheckResult::Failure => Err(format!("Checking the host key of {host} failed")),
+        }
+    }
+
+    fn warn_unverified_host_key(&self, reason: &str) {
+        if !self.unverified_host_key_logged.replace(true) {
+            log::warn!("{reason}. The SFTP server's host key is NOT verified");
+        }
+    }
+
+    /// Resolves a path relative to the album folder
+    fn album_path(&self, path: &str) -> Result<PathBuf, String> {
+        Ok(Path::new(&source::album_path(&self.server)?).join(path))
+    }
+}
+
+impl PhotoSource for Client {
+    fn list(&mut self, folder: &str) -> Result<Vec<String>, String> {
+        let folder_path = self.album_path(folder)?;
+        self.with_session(|sftp| {
+            let entries = sftp.readdir(&folder_path).map_err_to_string()?;
+            Ok(entries
+                .into_iter()
+                .filter(|(_, stat)| stat.is_file())
+                .filter_map(|(path, _)| Some(path.file_name()?.to_string_lossy().into_owned()))
+                .collect())
+        })
+    }
+
+    fn retrieve(&mut self, path: &str) -> Result<Bytes, String> {
+        let file_path = self.album_path(path)?;
+        self.with_session(|sftp| {
+            let mut file = sftp.open(&file_path).map_err_to_string()?;
+            let mut bytes = Vec::new();
+            file.read_to_end(&mut bytes).map_err_to_string()?;
+            Ok(Bytes::from(bytes))
+        })
+    }
+
+    fn is_file(&mut self, path: &str) -> bool {
+        let Ok(file_path) = self.album_path(path) else {
+            return false;
+        };
+        self.with_session(|sftp| sftp.stat(&file_path).map_err_to_string())
+            .is_ok_and(|stat| stat.is_file())
+    }
+
+    fn is_reachable(&self, timeout: Duration) -> bool {
+        source::is_reachable(&self.server, SSH_PORT, timeout)
+    }
+}
+
+/// Connects to the first of `addrs` accepting a connection within `timeout`
+fn connect_any(addrs: &[SocketAddr], timeout: Duration) -> Result<TcpStream, String> {
+    let mut last_error = "no address to connect to".to_string();
+    for addr in addrs {
+        match TcpStream::connect_timeout(addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(error) => last_error = format!("{addr}: {error}"),
+        }
+    }
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(server: &str, user: Option<&str>) -> Client {
+        Client::new(&server.parse().unwrap(), &user.map(str::to_string), &None)
+    }
+
+    #[test]
+    fn album_path_is_decoded_and_joined_with_path() {
+        let client = client(
+            "sftp://nas.lan/volume1/My%20Photos/%C3%89t%C3%A9",
+            Some("frame"),
+        );
+
+        let result = client.album_path("2024/a.jpg");
+
+        assert_eq!(
+            result,
+            Ok(PathBuf::from("/volume1/My Photos/Été/2024/a.jpg"))
+        );
+    }
+
+    #[test]
+    fn when_user_is_not_set_then_requests_fail_without_connecting() {
+        let mut client = client("sftp://nas.lan/photos", None);
+
+        let result = client.list("");
+
+        assert_eq!(result, Err("SFTP requires --user".to_string()));
+    }
+}
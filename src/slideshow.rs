@@ -1,18 +1,35 @@
 use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
     error::Error,
     fmt::{Display, Formatter},
+    fs,
+    iter::Peekable,
+    ops::Range,
+    path::Path,
+    str::Chars,
+    time::{Duration, Instant},
 };
 
 use bytes::Bytes;
-use ftp::FtpStream;
+use glob::Pattern;
 
 use crate::{
-    cli::{Order, SourceSize},
+    cli::{MergeMode, Order},
     // error::ErrorToString,
-    http::Url,
+    meta,
+    source::PhotoSource,
     Random,
 };
 
+/// Name of a file in the album folder listing glob patterns (one per line) of files to exclude
+/// from the slideshow
+const IGNORE_FILE_NAME: &str = ".frameignore";
+
+/// Suffix appended to a photo file name to form the name of a sidecar file containing the
+/// display duration of the photo in seconds
+const DURATION_SIDECAR_SUFFIX: &str = ".duration";
+
 #[derive(Clone, Copy, Debug)]
 pub enum SortBy {
     TakenTime,
@@ -20,17 +37,51 @@ pub enum SortBy {
 }
 
 
-/// Holds the slideshow state and queries API to fetch photos.
+/// Holds the slideshow state and queries the photo source to fetch photos.
 #[derive(Debug)]
-pub struct Slideshow<'a> {
-    ftp_server: &'a Url,
-    user: &'a Option<String>,
-    password: &'a Option<String>,
+pub struct Slideshow {
+    /// Paths of photos in the album, as listed during initialization
+    photos: Vec<String>,
+    /// Paths of duration sidecar files in the album, as listed during initialization
+    duration_sidecars: HashSet<String>,
     /// Indices of photos in an album in reverse order (so we can pop them off easily)
     photo_display_sequence: Vec<u32>,
-    order: Order,
+    /// Indices of photos fetched from the display sequence so far, in display order
+    fetched: Vec<u32>,
+    /// Album sub-folders (empty for the album folder itself) with their ordering
+    folders: Vec<(String, Order)>,
+    merge_mode: MergeMode,
     random_start: bool,
-    source_size: SourceSize,
+    /// Shuffle random ordered folders on every initialization, instead of reusing the order of
+    /// the first shuffle
+    random_reshuffle: bool,
+    /// Paths of photos in the order of the first shuffle, per folder. Kept unless reshuffling
+    shuffled_orders: HashMap<String, Vec<String>>,
+    /// Number of photos skipped at the start of each folder, in folder ordering
+    offset: usize,
+    /// Maximum number of photos displayed from each folder, following the skipped ones
+    limit: Option<usize>,
+    /// Path of the most recently fetched photo, kept across initializations
+    last_photo: Option<String>,
+    /// Time after which the album is listed again, even before the display sequence ends
+    reinit_interval: Option<Duration>,
+    initialized_at: Option<Instant>,
+    /// Path of a file listing the photos to display, replacing folder listing and ordering
+    manifest: Option<String>,
+    /// Paths of photos never to display
+    blacklist: HashSet<String>,
+}
+
+/// Photo file fetched from the album
+#[derive(Debug)]
+pub struct PhotoFile {
+    pub name: String,
+    pub bytes: Bytes,
+    /// Display duration read from the photo's sidecar file
+    pub display_duration: Option<Duration>,
+    /// Index of the photo in the album listing and number of photos left in the display
+    /// sequence, None for files not from the album
+    pub sequence_position: Option<(u32, usize)>,
 }
 
 #[derive(Debug)]
@@ -38,26 +89,38 @@ pub enum SlideshowError {
     Other(String),
 }
 
-impl<'a> Slideshow<'a> {
-    pub fn build(ftp_server: &'a Url, user: &'a Option<String>) -> Result<Slideshow<'a>, String> {
-        Ok(Slideshow {
-            ftp_server,
-            user,
-            password: &None,
+impl Slideshow {
+    pub fn new() -> Slideshow {
+        Slideshow {
+            photos: vec![],
+            duration_sidecars: HashSet::new(),
             photo_display_sequence: vec![],
-            order: Order::ByDate,
+            fetched: vec![],
+            folders: vec![(String::new(), Order::ByDate)],
+            merge_mode: MergeMode::RoundRobin,
             random_start: false,
-            source_size: SourceSize::L,
-        })
+            random_reshuffle: true,
+            shuffled_orders: HashMap::new(),
+            offset: 0,
+            limit: None,
+            last_photo: None,
+            reinit_interval: None,
+            initialized_at: None,
+            manifest: None,
+            blacklist: HashSet::new(),
+        }
     }
 
-    pub fn with_password(mut self, password: &'a Option<String>) -> Self {
-        self.password = password;
+    /// Sets the folders to combine, each with its own ordering. Photos from the folders are
+    /// displayed in turns, unless changed by [Slideshow::with_merge_mode]
+    pub fn with_folders(mut self, folders: Vec<(String, Order)>) -> Self {
+        assert!(!folders.is_empty(), "at least one folder is required");
+        self.folders = folders;
         self
     }
 
-    pub fn with_ordering(mut self, order: Order) -> Self {
-        self.order = order;
+    pub fn with_merge_mode(mut self, merge_mode: MergeMode) -> Self {
+        self.merge_mode = merge_mode;
         self
     }
 
@@ -66,58 +129,185 @@ impl<'a> Slideshow<'a> {
         self
     }
 
-    pub fn with_source_size(mut self, size: SourceSize) -> Self {
-        self.source_size = size;
+    /// Sets whether random ordered folders are shuffled again each time the slideshow restarts,
+    /// or keep the order of the first shuffle
+    pub fn with_random_reshuffle(mut self, random_reshuffle: bool) -> Self {
+        self.random_reshuffle = random_reshuffle;
         self
     }
 
-    fn get_photos_count(&self) -> u32 {
-        // Create a connection to FTP server
-        let ftp_connect = self.ftp_server.host_str().unwrap();
-        let mut ftp_stream = FtpStream::connect(format!("{}:21", ftp_connect)).unwrap();
-        let _ = ftp_stream.login(self.user.clone().unwrap().as_str(), self.password.clone().unwrap().as_str()).unwrap();
+    /// Restricts each folder to a window of photos, e.g. the 100 most recent ones. Random order
+    /// shuffles the photos within the window
+    pub fn with_window(mut self, offset: usize, limit: Option<usize>) -> Self {
+        self.offset = offset;
+        self.limit = limit;
+        self
+    }
 
-        
-        // Change into a new directory, relative to the one we are currently in.
-        let _ = ftp_stream.cwd(self.ftp_server.path()).unwrap();
+    /// Lists the album again at least once per `interval`, see [Slideshow::restart_if_due]
+    pub fn with_reinit_interval(mut self, interval: Option<Duration>) -> Self {
+        self.reinit_interval = interval;
+        self
+    }
 
-        // Fetch list of Photos
-        let photos = ftp_stream.nlst(None).unwrap();
+    /// Displays the photos listed in a manifest file (one path relative to the album folder per
+    /// line) in the listed order, instead of listing and ordering folders. The manifest is read
+    /// from the local file system when the path exists there, otherwise from the album folder, and
+    /// is read again each time the slideshow restarts
+    pub fn with_manifest(mut self, manifest: Option<String>) -> Self {
+        self.manifest = manifest;
+        self
+    }
 
-        // Terminate the connection to the server.
-        let _ = ftp_stream.quit();
-        photos.len() as u32
+    /// Excludes photos with the given paths from the slideshow
+    pub fn with_blacklist(mut self, blacklist: HashSet<String>) -> Self {
+        self.blacklist = blacklist;
+        self
     }
 
-    pub fn get_photo(&mut self, photo_index: u32) -> Result<Bytes, ()> {
-        // Create a connection to an FTP server and authenticate to it.
-        let ftp_connect = self.ftp_server.host_str().unwrap();
-        let mut ftp_stream = FtpStream::connect(format!("{}:21", ftp_connect)).unwrap();
-        let _ = ftp_stream.login(self.user.clone().unwrap().as_str(), self.password.clone().unwrap().as_str()).unwrap();
+    /// Excludes a photo from the rest of the display sequence and from later initializations
+    pub fn blacklist_photo(&mut self, photo_name: &str) {
+        if let Some(photo_index) = self.photos.iter().position(|photo| photo == photo_name) {
+            self.photo_display_sequence
+                .retain(|&index| index as usize != photo_index);
+            self.fetched.retain(|&index| index as usize != photo_index);
+        }
+        self.blacklist.insert(photo_name.to_string());
+    }
 
-        
-        // Change into a new directory, relative to the one we are currently in.
-        let _ = ftp_stream.cwd(self.ftp_server.path()).unwrap();
+    /// Lists paths of photos in a folder, excluding ones matching patterns in the folder's
+    /// [IGNORE_FILE_NAME]. A folder that turns out to be a file is a single photo
+    fn get_photos(source: &mut impl PhotoSource, folder: &str) -> Result<Vec<String>, String> {
+        let file_names = match source.list(folder) {
+            Ok(file_names) => file_names,
+            Err(error) if !folder.is_empty() && source.is_file(folder) => {
+                log::debug!("Listing {folder} failed, displaying it as a file: {error}");
+                return Ok(vec![folder.trim_end_matches('/').to_string()]);
+            }
+            Err(error) => return Err(error),
+        };
+        let ignore_file_path = join_path(folder, IGNORE_FILE_NAME);
+        let ignore_patterns = match source.retrieve(&ignore_file_path) {
+            Ok(bytes) => parse_ignore_patterns(&String::from_utf8_lossy(&bytes)),
+            Err(error) => {
+                log::debug!("{ignore_file_path} not loaded: {error}");
+                vec![]
+            }
+        };
+        Ok(file_names
+            .into_iter()
+            .filter(|name| !is_ignored(name, &ignore_patterns))
+            .map(|name| join_path(folder, &name))
+            .collect())
+    }
 
-        // Fetch list of Photos
-        let photos = ftp_stream.nlst(None).unwrap();
+    /// Reads paths of photos from the manifest, skipping ones missing from their folder listing.
+    /// Returns the paths in manifest order, and paths of duration sidecar files in the folders
+    fn get_manifest_photos(
+        source: &mut impl PhotoSource,
+        manifest: &str,
+    ) -> Result<(Vec<String>, Vec<String>), String> {
+        let manifest_file = if Path::new(manifest).is_file() {
+            fs::read_to_string(manifest).map_err(|error| format!("{manifest}: {error}"))?
+        } else {
+            let bytes = source
+                .retrieve(manifest)
+                .map_err(|error| format!("{manifest}: {error}"))?;
+            String::from_utf8_lossy(&bytes).into_owned()
+        };
+        let mut folder_listings: HashMap<&str, HashSet<String>> = HashMap::new();
+        let mut photos = vec![];
+        for path in manifest_file
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            let folder = path.rsplit_once('/').map_or("", |(folder, _)| folder);
+            if !folder_listings.contains_key(folder) {
+                let listing = match source.list(folder) {
+                    Ok(file_names) => file_names
+                        .iter()
+                        .map(|name| join_path(folder, name))
+                        .collect(),
+                    Err(error) => {
+                        log::warn!("{manifest}: listing {folder:?} failed: {error}");
+                        HashSet::new()
+                    }
+                };
+                folder_listings.insert(folder, listing);
+            }
+            if folder_listings[folder].contains(path) {
+                photos.push(path.to_string());
+            } else {
+                log::warn!("{manifest}: {path} not found, skipping");
+            }
+        }
+        let duration_sidecars = folder_listings
+            .into_values()
+            .flatten()
+            .filter(|path| path.ends_with(DURATION_SIDECAR_SUFFIX))
+            .collect();
+        Ok((photos, duration_sidecars))
+    }
 
-        // Retrieve (GET) a file from the FTP server in the current working directory.
-        let remote_file = Bytes::from(ftp_stream.simple_retr(photos.get(photo_index as usize).unwrap()).unwrap().into_inner());
+    /// Counts photos in all folders, excluding ignored files and sidecar files. With a manifest,
+    /// counts the listed photos that were found
+    pub fn get_photos_count(&self, source: &mut impl PhotoSource) -> Result<usize, String> {
+        if let Some(manifest) = &self.manifest {
+            return Ok(Slideshow::get_manifest_photos(source, manifest)?.0.len());
+        }
+        let mut count = 0;
+        for (folder, _) in &self.folders {
+            count += Slideshow::get_photos(source, folder)?
+                .iter()
+                .filter(|path| !path.ends_with(DURATION_SIDECAR_SUFFIX))
+                .count();
+        }
+        Ok(count)
+    }
 
+    pub fn get_photo(
+        &self,
+        source: &mut impl PhotoSource,
+        photo_index: u32,
+    ) -> Result<Bytes, String> {
+        let photo_name = self
+            .photos
+            .get(photo_index as usize)
+            .ok_or_else(|| format!("Photo {photo_index} not found"))?;
+        source.retrieve(photo_name)
+    }
 
-        // Terminate the connection to the server.
-        let _ = ftp_stream.quit();
-        Ok(remote_file)
+    /// Reads the display duration from the photo's sidecar file, if there is one
+    fn get_display_duration(
+        &self,
+        source: &mut impl PhotoSource,
+        photo_name: &str,
+    ) -> Option<Duration> {
+        let sidecar_name = format!("{photo_name}{DURATION_SIDECAR_SUFFIX}");
+        if !self.duration_sidecars.contains(&sidecar_name) {
+            return None;
+        }
+        let display_duration = source
+            .retrieve(&sidecar_name)
+            .ok()
+            .and_then(|bytes| meta::parse_display_duration(&String::from_utf8_lossy(&bytes)));
+        if display_duration.is_none() {
+            log::warn!("{sidecar_name}: failed to read duration");
+        }
+        display_duration
     }
 
+    /// Returns the next photo to display
     pub fn get_next_photo(
         &mut self,
+        source: &mut impl PhotoSource,
         random: Random,
-    ) -> Result<Bytes, SlideshowError> {
+    ) -> Result<PhotoFile, SlideshowError> {
         loop {
-            if self.slideshow_ended() {
-                self.initialize(random)?;
+            let is_fresh_listing = self.slideshow_ended();
+            if is_fresh_listing {
+                self.initialize(source, random)?;
             }
 
             let photo_index = self
@@ -125,58 +315,332 @@ impl<'a> Slideshow<'a> {
                 .pop()
                 .expect("photos should not be empty");
 
-            let photo_bytes_result = self.get_photo(photo_index);
+            let photo_bytes_result = self.get_photo(source, photo_index);
             match photo_bytes_result {
-                Ok(photo_bytes) => break Ok(photo_bytes),
-                Err(_) => { 
-                    /* Photos were removed from the album since we fetched its item_count. Reinitialize */
+                Ok(photo_bytes) => {
+                    let name = self.photos[photo_index as usize].clone();
+                    self.last_photo = Some(name.clone());
+                    self.fetched.push(photo_index);
+                    /* Helps to tell truncated transfers from corrupted files when decoding fails */
+                    if log::log_enabled!(log::Level::Debug) {
+                        log::debug!(
+                            "Fetched {name}: {} bytes, CRC32 {:08x}",
+                            photo_bytes.len(),
+                            crc32fast::hash(&photo_bytes)
+                        );
+                    }
+                    break Ok(PhotoFile {
+                        display_duration: self.get_display_duration(source, &name),
+                        name,
+                        bytes: photo_bytes,
+                        sequence_position: Some((photo_index, self.photo_display_sequence.len())),
+                    });
+                }
+                /* Retrieving fails even though the album has just been listed, e.g. because the
+                 * file is not readable. Returned, so that the fetcher retries with a delay */
+                Err(error) if is_fresh_listing => break Err(error.into()),
+                Err(error) => {
+                    /* Photos were removed from the album since we listed it. Reinitialize */
+                    log::debug!("{error}");
                     self.photo_display_sequence.clear();
-                    continue; 
-                },
+                    continue;
+                }
+            }
+        }
+    }
+
+    /// Returns the photo fetched before `photo_name` in the current display sequence, walking the
+    /// sequence backward when going back past the history. The next photo fetched by
+    /// [Slideshow::get_next_photo] stays the same
+    pub fn get_previous_photo(
+        &self,
+        source: &mut impl PhotoSource,
+        photo_name: &str,
+    ) -> Result<PhotoFile, SlideshowError> {
+        let position = self
+            .fetched
+            .iter()
+            .rposition(|&index| self.photos[index as usize] == photo_name)
+            .ok_or_else(|| format!("{photo_name} is not in the current display sequence"))?;
+        let photo_index = *position
+            .checked_sub(1)
+            .and_then(|previous| self.fetched.get(previous))
+            .ok_or_else(|| format!("{photo_name} is the first photo of the display sequence"))?;
+        let photo_bytes = self.get_photo(source, photo_index)?;
+        let name = self.photos[photo_index as usize].clone();
+        Ok(PhotoFile {
+            display_duration: self.get_display_duration(source, &name),
+            name,
+            bytes: photo_bytes,
+            sequence_position: Some((
+                photo_index,
+                self.fetched.len() - position + self.photo_display_sequence.len(),
+            )),
+        })
+    }
+
+    /// Drops the rest of the display sequence when the album was listed longer ago than the
+    /// reinitialization interval, so that the next photo is fetched from a fresh listing. Returns
+    /// true when the sequence was dropped
+    pub fn restart_if_due(&mut self) -> bool {
+        match (self.reinit_interval, self.initialized_at) {
+            (Some(interval), Some(initialized_at))
+                if !self.slideshow_ended() && initialized_at.elapsed() >= interval =>
+            {
+                log::info!("Listing the album again after {interval:?}");
+                self.photo_display_sequence.clear();
+                true
             }
+            _ => false,
         }
     }
 
-    fn slideshow_ended(&self) -> bool {
+    /// Returns the path of the photo [Slideshow::get_next_photo] fetches next, without fetching
+    /// it. None when the album is going to be listed again first
+    pub fn peek_next_filename(&self) -> Option<&str> {
+        self.photo_display_sequence
+            .last()
+            .and_then(|&photo_index| self.photos.get(photo_index as usize))
+            .map(String::as_str)
+    }
+
+    /// Whether all photos listed during initialization have been fetched
+    pub fn slideshow_ended(&self) -> bool {
         self.photo_display_sequence.is_empty()
     }
 
-    fn initialize(
-        &mut self,
-        (rand_gen_range, rand_shuffle): Random,
-    ) -> Result<(), String> {
+    fn initialize(&mut self, source: &mut impl PhotoSource, random: Random) -> Result<(), String> {
         assert!(
             self.photo_display_sequence.is_empty(),
             "already initialized"
         );
-        let item_count = self.get_photos_count();
-        if item_count < 1 {
+        self.photos.clear();
+        self.duration_sidecars.clear();
+        self.fetched.clear();
+        let folder_sequences = match &self.manifest {
+            Some(manifest) => {
+                let (mut photos, duration_sidecars) =
+                    Slideshow::get_manifest_photos(source, manifest)?;
+                photos.retain(|photo| !self.blacklist.contains(photo));
+                self.photos = photos;
+                self.duration_sidecars.extend(duration_sidecars);
+                vec![(0..self.photos.len() as u32).collect()]
+            }
+            None => self.folder_sequences(source, random)?,
+        };
+        if self.photos.is_empty() {
             return Err("Album is empty".to_string());
         }
-        self.photo_display_sequence.reserve(item_count as usize);
-        let photos_range = 0..item_count;
-        match self.order {
-            Order::ByDate | Order::ByName => {
-                if self.random_start {
-                    self.photo_display_sequence.extend(
-                        photos_range
-                            .skip(rand_gen_range(0..item_count) as usize)
-                            .rev(),
+        /* RandomStart is only used when slideshow starts, and afterward continues in normal order */
+        self.random_start = false;
+        self.photo_display_sequence = match self.merge_mode {
+            MergeMode::RoundRobin => interleave(folder_sequences),
+            MergeMode::Concat => folder_sequences.concat(),
+        };
+        self.photo_display_sequence.reverse();
+        self.initialized_at = Some(Instant::now());
+
+        Ok(())
+    }
+
+    /// Lists and orders photos of each folder. Returns the display sequence of each folder
+    fn folder_sequences(
+        &mut self,
+        source: &mut impl PhotoSource,
+        random: Random,
+    ) -> Result<Vec<Vec<u32>>, String> {
+        let mut folder_sequences = Vec::with_capacity(self.folders.len());
+        for (folder, order) in &self.folders {
+            let (duration_sidecars, mut photos): (Vec<_>, Vec<_>) =
+                Slideshow::get_photos(source, folder)?
+                    .into_iter()
+                    .partition(|path| path.ends_with(DURATION_SIDECAR_SUFFIX));
+            photos.retain(|photo| !self.blacklist.contains(photo));
+            if *order == Order::ByName {
+                photos.sort_by(|a, b| natural_cmp(a, b));
+            }
+            photos.drain(..self.offset.min(photos.len()));
+            if let Some(limit) = self.limit {
+                photos.truncate(limit);
+            }
+            let first_index = self.photos.len() as u32;
+            let last_photo_index = self
+                .last_photo
+                .as_ref()
+                .and_then(|last_photo| photos.iter().position(|photo| photo == last_photo))
+                .map(|position| first_index + position as u32);
+            self.photos.extend(photos);
+            self.duration_sidecars.extend(duration_sidecars);
+            let photos_range = first_index..self.photos.len() as u32;
+            let kept_order = self
+                .shuffled_orders
+                .get(folder)
+                .filter(|_| *order == Order::Random && !self.random_reshuffle);
+            let sequence = match kept_order {
+                Some(kept_order) => kept_sequence(photos_range, &self.photos, kept_order),
+                None => {
+                    let sequence = folder_sequence(
+                        photos_range,
+                        *order,
+                        self.random_start,
+                        last_photo_index,
+                        random,
                     );
-                    /* RandomStart is only used when slideshow starts, and afterward continues in normal order */
-                    self.random_start = false;
-                } else {
-                    self.photo_display_sequence.extend(photos_range.rev());
+                    if *order == Order::Random && !self.random_reshuffle {
+                        self.shuffled_orders.insert(
+                            folder.clone(),
+                            sequence
+                                .iter()
+                                .map(|&index| self.photos[index as usize].clone())
+                                .collect(),
+                        );
+                    }
+                    sequence
                 }
+            };
+            folder_sequences.push(sequence);
+        }
+        Ok(folder_sequences)
+    }
+}
+
+/// Orders indices of photos from a single folder in display order. A random order never starts
+/// with `last_photo_index`, so that the photo ending the previous sequence is not displayed twice
+/// in a row
+fn folder_sequence(
+    photos_range: Range<u32>,
+    order: Order,
+    random_start: bool,
+    last_photo_index: Option<u32>,
+    (rand_gen_range, rand_shuffle): Random,
+) -> Vec<u32> {
+    if photos_range.is_empty() {
+        return vec![];
+    }
+    match order {
+        Order::ByDate | Order::ByName => {
+            let skipped = if random_start {
+                rand_gen_range(0..photos_range.len() as u32) as usize
+            } else {
+                0
+            };
+            photos_range.skip(skipped).collect()
+        }
+        Order::Random => {
+            let mut sequence: Vec<u32> = photos_range.collect();
+            rand_shuffle(&mut sequence);
+            if sequence.len() > 1 && sequence.first() == last_photo_index.as_ref() {
+                let last = sequence.len() - 1;
+                sequence.swap(0, last);
             }
-            Order::Random => {
-                self.photo_display_sequence.extend(photos_range);
-                rand_shuffle(&mut self.photo_display_sequence)
+            sequence
+        }
+    }
+}
+
+/// Orders indices of photos from a single folder as in `kept_order`. Photos missing from it come
+/// last, in listing order
+fn kept_sequence(photos_range: Range<u32>, photos: &[String], kept_order: &[String]) -> Vec<u32> {
+    let positions: HashMap<_, _> = kept_order
+        .iter()
+        .enumerate()
+        .map(|(position, path)| (path.as_str(), position))
+        .collect();
+    let mut sequence: Vec<u32> = photos_range.collect();
+    sequence.sort_by_key(|&index| {
+        positions
+            .get(photos[index as usize].as_str())
+            .copied()
+            .unwrap_or(usize::MAX)
+    });
+    sequence
+}
+
+/// Merges sequences by taking one item from each in turn, until all of them are exhausted
+fn interleave(sequences: Vec<Vec<u32>>) -> Vec<u32> {
+    let item_count = sequences.iter().map(Vec::len).sum();
+    let mut merged = Vec::with_capacity(item_count);
+    let mut iterators: Vec<_> = sequences.into_iter().map(Vec::into_iter).collect();
+    while merged.len() < item_count {
+        for iterator in &mut iterators {
+            merged.extend(iterator.next());
+        }
+    }
+    merged
+}
+
+/// Joins a folder relative to the album folder with a file name
+fn join_path(folder: &str, file_name: &str) -> String {
+    match folder.trim_end_matches('/') {
+        "" => file_name.to_string(),
+        folder => format!("{folder}/{file_name}"),
+    }
+}
+
+impl Default for Slideshow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_ignore_patterns(ignore_file: &str) -> Vec<Pattern> {
+    ignore_file
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| match Pattern::new(line) {
+            Ok(pattern) => Some(pattern),
+            Err(error) => {
+                log::warn!("{IGNORE_FILE_NAME}: invalid pattern {line}: {error}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Compares file names so that numbers embedded in them are ordered by value, e.g. "IMG_2.jpg"
+/// before "IMG_10.jpg". Letters are compared case-insensitively
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_chars, mut b_chars) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let ordering = match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_char), Some(b_char)) if a_char.is_ascii_digit() && b_char.is_ascii_digit() => {
+                let (a_number, b_number) = (take_number(&mut a_chars), take_number(&mut b_chars));
+                /* Without leading zeros, a longer number is greater */
+                a_number
+                    .len()
+                    .cmp(&b_number.len())
+                    .then_with(|| a_number.cmp(&b_number))
             }
+            (Some(a_char), Some(b_char)) => {
+                let ordering = a_char.to_lowercase().cmp(b_char.to_lowercase());
+                a_chars.next();
+                b_chars.next();
+                ordering
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
         }
+    }
+}
 
-        Ok(())
+/// Consumes a run of digits, returning it without leading zeros
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut number = String::new();
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        if !(number.is_empty() && digit == '0') {
+            number.push(digit);
+        }
     }
+    number
+}
+
+fn is_ignored(file_name: &str, ignore_patterns: &[Pattern]) -> bool {
+    file_name == IGNORE_FILE_NAME || ignore_patterns.iter().any(|p| p.matches(file_name))
 }
 
 impl From<Order> for SortBy {
@@ -206,6 +670,538 @@ impl From<String> for SlideshowError {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MockPhotoSource;
+
+    const DUMMY_RANDOM: Random = (|_| 42, |_| ());
+
+    #[test]
+    fn files_matching_ignore_patterns_are_ignored() {
+        let ignore_patterns = parse_ignore_patterns(
+            "# Comment\n\
+             \n\
+             *.txt\n  \
+             private_?.jpg  \n\
+             thumbs/*\n",
+        );
+
+        assert_eq!(ignore_patterns.len(), 3);
+        assert!(is_ignored("notes.txt", &ignore_patterns));
+        assert!(is_ignored("private_1.jpg", &ignore_patterns));
+        assert!(!is_ignored("private_12.jpg", &ignore_patterns));
+        assert!(!is_ignored("photo.jpg", &ignore_patterns));
+        assert!(!is_ignored("# Comment", &ignore_patterns));
+    }
+
+    #[test]
+    fn ignore_file_itself_is_ignored() {
+        assert!(is_ignored(IGNORE_FILE_NAME, &[]));
+    }
+
+    #[test]
+    fn natural_cmp_orders_numbers_by_value() {
+        let mut file_names = [
+            "IMG_10.jpg",
+            "img_3.jpg",
+            "IMG_2.jpg",
+            "IMG_002b.jpg",
+            "IMG_1.jpg",
+            "IMG.jpg",
+        ];
+
+        file_names.sort_by(|a, b| natural_cmp(a, b));
+
+        assert_eq!(
+            file_names,
+            [
+                "IMG.jpg",
+                "IMG_1.jpg",
+                "IMG_2.jpg",
+                "IMG_002b.jpg",
+                "img_3.jpg",
+                "IMG_10.jpg"
+            ]
+        );
+    }
+
+    #[test]
+    fn when_ordered_by_name_then_photos_are_sorted_naturally() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub.expect_list().returning(|_| {
+            Ok(["b_10.jpg", "a.jpg", "b_9.jpg"]
+                .map(str::to_string)
+                .to_vec())
+        });
+        source_stub
+            .expect_retrieve()
+            .returning(|_| Err("550 File not found".to_string()));
+        let mut slideshow = Slideshow::new().with_folders(vec![(String::new(), Order::ByName)]);
+
+        slideshow.initialize(&mut source_stub, DUMMY_RANDOM).unwrap();
+
+        assert_eq!(slideshow.photos, ["a.jpg", "b_9.jpg", "b_10.jpg"]);
+    }
+
+    #[test]
+    fn when_folders_have_own_ordering_then_their_photos_are_interleaved() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub
+            .expect_list()
+            .withf(|folder| folder == "2023")
+            .returning(|_| {
+                Ok(["b_10.jpg", "b_9.jpg", "a.jpg"]
+                    .map(str::to_string)
+                    .to_vec())
+            });
+        source_stub
+            .expect_list()
+            .withf(|folder| folder == "2024/")
+            .returning(|_| Ok(["x.jpg", "y.jpg"].map(str::to_string).to_vec()));
+        source_stub
+            .expect_retrieve()
+            .withf(|path| path.ends_with(IGNORE_FILE_NAME))
+            .returning(|_| Err("550 File not found".to_string()));
+        let mut slideshow = Slideshow::new().with_folders(vec![
+            ("2023".to_string(), Order::ByName),
+            ("2024/".to_string(), Order::Random),
+        ]);
+        const REVERSING_RANDOM: Random = (|_| 0, |slice| slice.reverse());
+
+        slideshow.initialize(&mut source_stub, REVERSING_RANDOM).unwrap();
+
+        let display_order: Vec<_> = slideshow
+            .photo_display_sequence
+            .iter()
+            .rev()
+            .map(|&index| slideshow.photos[index as usize].as_str())
+            .collect();
+        assert_eq!(
+            display_order,
+            [
+                "2023/a.jpg",
+                "2024/y.jpg",
+                "2023/b_9.jpg",
+                "2024/x.jpg",
+                "2023/b_10.jpg"
+            ]
+        );
+    }
+
+    #[test]
+    fn when_merge_mode_is_concat_then_folders_are_displayed_one_after_another() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub
+            .expect_list()
+            .withf(|folder| folder == "2023")
+            .returning(|_| Ok(["a.jpg", "b.jpg"].map(str::to_string).to_vec()));
+        source_stub
+            .expect_list()
+            .withf(|folder| folder == "2024")
+            .returning(|_| Ok(["x.jpg", "y.jpg"].map(str::to_string).to_vec()));
+        source_stub
+            .expect_retrieve()
+            .withf(|path| path.ends_with(IGNORE_FILE_NAME))
+            .returning(|_| Err("550 File not found".to_string()));
+        let mut slideshow = Slideshow::new()
+            .with_folders(vec![
+                ("2023".to_string(), Order::ByName),
+                ("2024".to_string(), Order::ByName),
+            ])
+            .with_merge_mode(MergeMode::Concat);
+
+        slideshow.initialize(&mut source_stub, DUMMY_RANDOM).unwrap();
+
+        let display_order: Vec<_> = slideshow
+            .photo_display_sequence
+            .iter()
+            .rev()
+            .map(|&index| slideshow.photos[index as usize].as_str())
+            .collect();
+        assert_eq!(
+            display_order,
+            ["2023/a.jpg", "2023/b.jpg", "2024/x.jpg", "2024/y.jpg"]
+        );
+    }
+
+    #[test]
+    fn when_manifest_is_set_then_listed_photos_are_displayed_in_manifest_order() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub
+            .expect_retrieve()
+            .withf(|path| path == "frame-manifest.txt")
+            .returning(|_| Ok(Bytes::from_static(b"b.jpg\nmissing.jpg\n\n2024/x.jpg\na.jpg\n")));
+        source_stub
+            .expect_list()
+            .withf(|folder| folder.is_empty())
+            .returning(|_| Ok(["a.jpg", "b.jpg", "c.jpg"].map(str::to_string).to_vec()));
+        source_stub
+            .expect_list()
+            .withf(|folder| folder == "2024")
+            .returning(|_| Ok(["x.jpg", "x.jpg.duration"].map(str::to_string).to_vec()));
+        let mut slideshow = Slideshow::new()
+            .with_random_start(true)
+            .with_manifest(Some("frame-manifest.txt".to_string()));
+
+        slideshow.initialize(&mut source_stub, DUMMY_RANDOM).unwrap();
+
+        let display_order: Vec<_> = slideshow
+            .photo_display_sequence
+            .iter()
+            .rev()
+            .map(|&index| slideshow.photos[index as usize].as_str())
+            .collect();
+        assert_eq!(display_order, ["b.jpg", "2024/x.jpg", "a.jpg"]);
+        assert!(slideshow.duration_sidecars.contains("2024/x.jpg.duration"));
+    }
+
+    #[test]
+    fn blacklisted_photos_are_not_displayed() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub
+            .expect_list()
+            .returning(|_| Ok(["a.jpg", "b.jpg", "c.jpg"].map(str::to_string).to_vec()));
+        source_stub
+            .expect_retrieve()
+            .withf(|path| path == IGNORE_FILE_NAME)
+            .returning(|_| Err("550 File not found".to_string()));
+        let mut slideshow = Slideshow::new().with_blacklist(HashSet::from(["a.jpg".to_string()]));
+        slideshow.initialize(&mut source_stub, DUMMY_RANDOM).unwrap();
+
+        slideshow.blacklist_photo("c.jpg");
+
+        assert_eq!(slideshow.photos, ["b.jpg", "c.jpg"]);
+        assert_eq!(slideshow.photo_display_sequence, [0]);
+        slideshow.photo_display_sequence.clear();
+        slideshow.initialize(&mut source_stub, DUMMY_RANDOM).unwrap();
+        assert_eq!(slideshow.photos, ["b.jpg"]);
+    }
+
+    #[test]
+    fn when_window_is_set_then_only_photos_within_it_are_displayed() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub.expect_list().returning(|_| {
+            Ok(["e.jpg", "d.jpg", "c.jpg", "b.jpg", "a.jpg"]
+                .map(str::to_string)
+                .to_vec())
+        });
+        source_stub
+            .expect_retrieve()
+            .returning(|_| Err("550 File not found".to_string()));
+        let mut slideshow = Slideshow::new()
+            .with_folders(vec![(String::new(), Order::ByName)])
+            .with_window(1, Some(3))
+            .with_random_start(true);
+        const LAST_RANDOM: Random = (|range| range.end - 1, |_| ());
+
+        slideshow.initialize(&mut source_stub, LAST_RANDOM).unwrap();
+
+        assert_eq!(slideshow.photos, ["b.jpg", "c.jpg", "d.jpg"]);
+        assert_eq!(slideshow.peek_next_filename(), Some("d.jpg"));
+    }
+
+    #[test]
+    fn when_reinit_interval_elapsed_then_display_sequence_is_dropped() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub
+            .expect_list()
+            .returning(|_| Ok(["a.jpg", "b.jpg"].map(str::to_string).to_vec()));
+        source_stub
+            .expect_retrieve()
+            .returning(|_| Err("550 File not found".to_string()));
+        let mut never_due = Slideshow::new().with_reinit_interval(None);
+        let mut due = Slideshow::new().with_reinit_interval(Some(Duration::ZERO));
+        never_due.initialize(&mut source_stub, DUMMY_RANDOM).unwrap();
+        due.initialize(&mut source_stub, DUMMY_RANDOM).unwrap();
+
+        assert!(!never_due.restart_if_due());
+        assert!(!never_due.slideshow_ended());
+        assert!(due.restart_if_due());
+        assert!(due.slideshow_ended());
+    }
+
+    #[test]
+    fn when_random_reshuffle_is_off_then_first_shuffle_order_is_kept() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub.expect_list().returning(|_| {
+            Ok(["a.jpg", "b.jpg", "c.jpg"]
+                .map(str::to_string)
+                .to_vec())
+        });
+        source_stub
+            .expect_retrieve()
+            .returning(|_| Err("550 File not found".to_string()));
+        let mut slideshow = Slideshow::new()
+            .with_folders(vec![(String::new(), Order::Random)])
+            .with_random_reshuffle(false);
+        const REVERSING_RANDOM: Random = (|_| 0, |slice| slice.reverse());
+        slideshow.initialize(&mut source_stub, REVERSING_RANDOM).unwrap();
+        let first_sequence = slideshow.photo_display_sequence.clone();
+        slideshow.photo_display_sequence.clear();
+
+        /* DUMMY_RANDOM does not shuffle, so a new shuffle would differ */
+        slideshow.initialize(&mut source_stub, DUMMY_RANDOM).unwrap();
+
+        assert_eq!(slideshow.photo_display_sequence, first_sequence);
+    }
+
+    #[test]
+    fn when_random_reshuffle_starts_with_last_photo_then_it_is_moved_away_from_front() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub.expect_list().returning(|_| {
+            Ok(["a.jpg", "b.jpg", "c.jpg"]
+                .map(str::to_string)
+                .to_vec())
+        });
+        source_stub
+            .expect_retrieve()
+            .returning(|_| Err("550 File not found".to_string()));
+        let mut slideshow = Slideshow::new().with_folders(vec![(String::new(), Order::Random)]);
+        slideshow.last_photo = Some("a.jpg".to_string());
+
+        /* DUMMY_RANDOM does not shuffle, so "a.jpg" would be first */
+        slideshow.initialize(&mut source_stub, DUMMY_RANDOM).unwrap();
+
+        let display_order: Vec<_> = slideshow
+            .photo_display_sequence
+            .iter()
+            .rev()
+            .map(|&index| slideshow.photos[index as usize].as_str())
+            .collect();
+        assert_eq!(display_order, ["c.jpg", "b.jpg", "a.jpg"]);
+    }
+
+    #[test]
+    fn when_photo_has_duration_sidecar_then_its_display_duration_is_returned() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub.expect_list().returning(|_| {
+            Ok(["photo1.jpg", "photo1.jpg.duration", "photo2.jpg"]
+                .map(str::to_string)
+                .to_vec())
+        });
+        source_stub
+            .expect_retrieve()
+            .withf(|file_name| file_name == "photo1.jpg.duration")
+            .returning(|_| Ok(Bytes::from_static(b"60\n")));
+        source_stub
+            .expect_retrieve()
+            .withf(|file_name| file_name.ends_with(".jpg"))
+            .returning(|_| Ok(Bytes::from_static(&[1])));
+        source_stub
+            .expect_retrieve()
+            .withf(|file_name| file_name == IGNORE_FILE_NAME)
+            .returning(|_| Err("550 File not found".to_string()));
+        let mut slideshow = Slideshow::new();
+
+        let first = slideshow.get_next_photo(&mut source_stub, DUMMY_RANDOM);
+        let second = slideshow.get_next_photo(&mut source_stub, DUMMY_RANDOM);
+
+        assert_eq!(slideshow.photos, ["photo1.jpg", "photo2.jpg"]);
+        assert_eq!(first.unwrap().display_duration, Some(Duration::from_secs(60)));
+        assert_eq!(second.unwrap().display_duration, None);
+    }
+
+    #[test]
+    fn peek_next_filename_returns_next_photo_without_fetching_it() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub
+            .expect_list()
+            .returning(|_| Ok(["a.jpg", "b.jpg"].map(str::to_string).to_vec()));
+        source_stub
+            .expect_retrieve()
+            .withf(|path| path == IGNORE_FILE_NAME)
+            .returning(|_| Err("550 File not found".to_string()));
+        source_stub
+            .expect_retrieve()
+            .withf(|path| path == "a.jpg")
+            .once()
+            .returning(|_| Ok(Bytes::from_static(&[1])));
+        let mut slideshow = Slideshow::new();
+        assert_eq!(slideshow.peek_next_filename(), None);
+
+        slideshow.get_next_photo(&mut source_stub, DUMMY_RANDOM).unwrap();
+
+        assert_eq!(slideshow.peek_next_filename(), Some("b.jpg"));
+        assert_eq!(slideshow.peek_next_filename(), Some("b.jpg"));
+    }
+
+    #[test]
+    fn when_folder_is_a_file_then_it_is_the_only_photo() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub
+            .expect_list()
+            .returning(|_| Err("550 No such directory".to_string()));
+        source_stub
+            .expect_is_file()
+            .withf(|path| path == "signage/welcome.jpg")
+            .return_const(true);
+        let mut slideshow =
+            Slideshow::new().with_folders(vec![("signage/welcome.jpg".to_string(), Order::ByName)]);
+
+        slideshow.initialize(&mut source_stub, DUMMY_RANDOM).unwrap();
+
+        assert_eq!(slideshow.photos, ["signage/welcome.jpg"]);
+    }
+
+    #[test]
+    fn get_next_photo_returns_sequence_position() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub
+            .expect_list()
+            .returning(|_| Ok(["a.jpg", "b.jpg", "c.jpg"].map(str::to_string).to_vec()));
+        source_stub
+            .expect_retrieve()
+            .withf(|path| path == IGNORE_FILE_NAME)
+            .returning(|_| Err("550 File not found".to_string()));
+        source_stub
+            .expect_retrieve()
+            .returning(|_| Ok(Bytes::from_static(&[1])));
+        let mut slideshow = Slideshow::new().with_folders(vec![(String::new(), Order::Random)]);
+        const REVERSING_RANDOM: Random = (|_| 0, |slice| slice.reverse());
+
+        let first = slideshow
+            .get_next_photo(&mut source_stub, REVERSING_RANDOM)
+            .unwrap();
+        let second = slideshow
+            .get_next_photo(&mut source_stub, REVERSING_RANDOM)
+            .unwrap();
+
+        assert_eq!(first.sequence_position, Some((2, 2)));
+        assert_eq!(second.sequence_position, Some((1, 1)));
+    }
+
+    #[test]
+    fn get_previous_photo_walks_sequence_backward_without_moving_next_photo() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub
+            .expect_list()
+            .returning(|_| Ok(["a.jpg", "b.jpg", "c.jpg"].map(str::to_string).to_vec()));
+        source_stub
+            .expect_retrieve()
+            .withf(|path| path == IGNORE_FILE_NAME)
+            .returning(|_| Err("550 File not found".to_string()));
+        source_stub
+            .expect_retrieve()
+            .returning(|path| Ok(Bytes::from(path.to_string())));
+        let mut slideshow = Slideshow::new();
+        for _ in 0..2 {
+            slideshow
+                .get_next_photo(&mut source_stub, DUMMY_RANDOM)
+                .unwrap();
+        }
+
+        let previous = slideshow
+            .get_previous_photo(&mut source_stub, "b.jpg")
+            .unwrap();
+
+        assert_eq!(previous.name, "a.jpg");
+        assert_eq!(previous.bytes, Bytes::from_static(b"a.jpg"));
+        assert_eq!(previous.sequence_position, Some((0, 2)));
+        assert!(slideshow
+            .get_previous_photo(&mut source_stub, "a.jpg")
+            .is_err());
+        assert!(slideshow
+            .get_previous_photo(&mut source_stub, "c.jpg")
+            .is_err());
+        assert_eq!(slideshow.peek_next_filename(), Some("c.jpg"));
+    }
+
+    #[test]
+    fn get_photo_returns_error_when_index_exceeds_listing() {
+        let mut source_stub = MockPhotoSource::new();
+        source_stub.expect_retrieve().never();
+        let mut slideshow = Slideshow::new();
+        slideshow.photos = vec!["photo1.jpg".to_string()];
+
+        let result = slideshow.get_photo(&mut source_stub, 1);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn when_photos_are_removed_during_slideshow_then_get_next_photo_reinitializes() {
+        /* Arrange */
+        let mut source_mock = MockPhotoSource::new();
+        source_mock.expect_list().times(1).returning(|_| {
+            Ok(vec![
+                "photo1.jpg".to_string(),
+                "photo2.jpg".to_string(),
+                "photo3.jpg".to_string(),
+            ])
+        });
+        source_mock.expect_list().times(1).returning(|_| Ok(vec!["photo3.jpg".to_string()]));
+        source_mock
+            .expect_retrieve()
+            .withf(|file_name| file_name == IGNORE_FILE_NAME)
+            .returning(|_| Err("550 File not found".to_string()));
+        source_mock
+            .expect_retrieve()
+            .withf(|file_name| file_name == "photo1.jpg")
+            .times(1)
+            .returning(|_| Ok(Bytes::from_static(&[1])));
+        source_mock
+            .expect_retrieve()
+            .withf(|file_name| file_name == "photo2.jpg")
+            .times(1)
+            .returning(|_| Err("550 File not found".to_string()));
+        source_mock
+            .expect_retrieve()
+            .withf(|file_name| file_name == "photo3.jpg")
+            .times(1)
+            .returning(|_| Ok(Bytes::from_static(&[3])));
+        let mut slideshow = Slideshow::new();
+        assert!(slideshow
+            .get_next_photo(&mut source_mock, DUMMY_RANDOM)
+            .is_ok());
+
+        /* Act */
+        let result = slideshow.get_next_photo(&mut source_mock, DUMMY_RANDOM);
+
+        /* Assert */
+        let photo_file = result.unwrap();
+        assert_eq!(photo_file.name, "photo3.jpg");
+        assert_eq!(photo_file.bytes, Bytes::from_static(&[3]));
+        assert!(slideshow.photo_display_sequence.is_empty());
+        source_mock.checkpoint();
+    }
+
+    #[test]
+    fn when_photo_cannot_be_retrieved_after_listing_then_get_next_photo_fails() {
+        let mut source_mock = MockPhotoSource::new();
+        source_mock
+            .expect_list()
+            .times(2)
+            .returning(|_| Ok(["photo1.jpg", "photo2.jpg"].map(str::to_string).to_vec()));
+        source_mock
+            .expect_retrieve()
+            .withf(|file_name| file_name == IGNORE_FILE_NAME)
+            .returning(|_| Err("550 File not found".to_string()));
+        source_mock
+            .expect_retrieve()
+            .withf(|file_name| file_name.starts_with("photo"))
+            .times(3)
+            .returning(|_| Err("550 Permission denied".to_string()));
+        let mut slideshow = Slideshow::new();
+
+        let first = slideshow.get_next_photo(&mut source_mock, DUMMY_RANDOM);
+        /* Fails on the remaining photo of the listing, then once more after listing again */
+        let second = slideshow.get_next_photo(&mut source_mock, DUMMY_RANDOM);
+
+        assert!(first.is_err());
+        assert!(second.is_err());
+        source_mock.checkpoint();
+    }
+
+    #[test]
+    fn invalid_ignore_patterns_are_skipped() {
+        let ignore_patterns = parse_ignore_patterns("[invalid\n*.png");
+
+        assert_eq!(ignore_patterns.len(), 1);
+        assert!(is_ignored("a.png", &ignore_patterns));
+    }
+}
+
 // /// These tests cover both `slideshow` and `api_photos` modules
 // #[cfg(test)]
 // mod tests {
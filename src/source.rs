@@ -0,0 +1,117 @@
+//! Photo source abstraction
+
+use std::{
+    borrow::Cow,
+    net::{SocketAddr, TcpStream},
+    time::Duration,
+};
+
+use bytes::Bytes;
+use percent_encoding::percent_decode_str;
+
+use crate::http::Url;
+
+/// Isolates the server storing the photos (e.g. [crate::ftp::Client]) for testing
+#[cfg_attr(test, mockall::automock)]
+pub trait PhotoSource {
+    /// Lists file names in a folder relative to the album folder (the album folder itself when
+    /// `folder` is empty)
+    fn list(&mut self, folder: &str) -> Result<Vec<String>, String>;
+
+    /// Retrieves a file by its path relative to the album folder
+    fn retrieve(&mut self, path: &str) -> Result<Bytes, String>;
+
+    /// Checks whether a path relative to the album folder is a file rather than a folder
+    fn is_file(&mut self, path: &str) -> bool;
+
+    /// Checks whether a connection to the server can be established within `timeout`
+    fn is_reachable(&self, timeout: Duration) -> bool;
+}
+
+impl<T: PhotoSource + ?Sized> PhotoSource for Box<T> {
+    fn list(&mut self, folder: &str) -> Result<Vec<String>, String> {
+        (**self).list(folder)
+    }
+
+    fn retrieve(&mut self, path: &str) -> Result<Bytes, String> {
+        (**self).retrieve(path)
+    }
+
+    fn is_file(&mut self, path: &str) -> bool {
+        (**self).is_file(path)
+    }
+
+    fn is_reachable(&self, timeout: Duration) -> bool {
+        (**self).is_reachable(timeout)
+    }
+}
+
+/// Checks whether a TCP connection to the server can be established within `timeout`.
+/// `default_port` is used when the server URL does not specify one
+pub(crate) fn is_reachable(server: &Url, default_port: u16, timeout: Duration) -> bool {
+    match socket_addrs(server, default_port) {
+        Ok(addrs) => addrs
+            .iter()
+            .any(|addr| TcpStream::connect_timeout(addr, timeout).is_ok()),
+        Err(error) => {
+            log::debug!("{error}");
+            false
+        }
+    }
+}
+
+/// Resolves the server host, which may be an IPv6 literal in brackets (e.g. `ftp://[fd00::2]/`).
+/// IPv6 addresses come first when the host resolves to both IPv6 and IPv4 addresses.
+/// `default_port` is used when the server URL does not specify one
+pub(crate) fn socket_addrs(server: &Url, default_port: u16) -> Result<Vec<SocketAddr>, String> {
+    let mut addrs = server
+        .socket_addrs(|| Some(default_port))
+        .map_err(|error| format!("{}: {error}", server.host_str().unwrap_or_default()))?;
+    /* Stable sort keeps the resolver's order within each family */
+    addrs.sort_by_key(SocketAddr::is_ipv4);
+    Ok(addrs)
+}
+
+/// Path of the album folder on the server. URLs keep non-ASCII characters and spaces
+/// percent-encoded, while servers expect the path as is, e.g. `/Fotos/Été` rather than
+/// `/Fotos/%C3%89t%C3%A9`
+pub(crate) fn album_path(server: &Url) -> Result<String, String> {
+    percent_decode_str(server.path())
+        .decode_utf8()
+        .map(Cow::into_owned)
+        .map_err(|error| format!("{}: {error}", server.path()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    #[test]
+    fn socket_addrs_accepts_ipv6_literal() {
+        let server = "ftp://[fd00::2]/photos".parse().unwrap();
+
+        let result = socket_addrs(&server, 21);
+
+        let ip = Ipv6Addr::new(0xfd00, 0, 0, 0, 0, 0, 0, 2);
+        assert_eq!(result, Ok(vec![SocketAddr::from((ip, 21))]));
+    }
+
+    #[test]
+    fn album_path_is_percent_decoded() {
+        let server = "ftp://nas.lan/Fotos/%C3%89t%C3%A9 2024/".parse().unwrap();
+
+        assert_eq!(album_path(&server), Ok("/Fotos/Été 2024/".to_string()));
+    }
+
+    #[test]
+    fn socket_addrs_uses_port_from_url() {
+        let server = "ftp://192.168.1.20:2121/photos".parse().unwrap();
+
+        let result = socket_addrs(&server, 21);
+
+        let ip = Ipv4Addr::new(192, 168, 1, 20);
+        assert_eq!(result, Ok(vec![SocketAddr::from((ip, 2121))]));
+    }
+}
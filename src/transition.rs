@@ -1,4 +1,7 @@
-use std::fmt::{Display, Formatter};
+use std::{
+    fmt::{Display, Formatter},
+    ops::Range,
+};
 
 #[cfg(not(test))]
 use std::time::Instant;
@@ -9,7 +12,7 @@ use mock_instant::Instant;
 use crate::{
     cli::Transition,
     sdl::{Color, Sdl, TextureIndex},
-    QuitEvent,
+    FrameResult, QuitEvent,
 };
 
 const TRANSITION_ALPHA_MIN: f64 = 0_f64;
@@ -17,6 +20,42 @@ const TRANSITION_ALPHA_MAX: f64 = 255_f64;
 // Possibly parametrize this and take command line argument to control length of the transition
 const FADE_TO_BLACK_DURATION_SECS: f64 = 1_f64;
 const CROSSFADE_DURATION_SECS: f64 = 1_f64;
+/// Effects [Transition::Random] picks from
+const RANDOM_TRANSITIONS: [Transition; 2] = [Transition::Crossfade, Transition::FadeToBlack];
+
+/// Effect played when the next photo replaces the current one. It starts with the current photo
+/// on screen, loaded in [TextureIndex::Current], and has to end with the next photo, loaded in
+/// [TextureIndex::Next], presented on the canvas.
+///
+/// Built-in effects selected with --transition are [Transition] variants. Custom effects can be
+/// passed to [crate::run_with_effect]
+pub trait TransitionEffect {
+    /// Plays the effect. `canvas_color` is the --canvas-color effects may fade through
+    fn play(&self, sdl: &mut dyn Sdl, canvas_color: Color) -> FrameResult<()>;
+
+    /// Built-in effect this is, None for custom effects. The slideshow resolves
+    /// [Transition::Random] with [TransitionPicker] instead of playing it
+    fn transition(&self) -> Option<Transition> {
+        None
+    }
+}
+
+impl TransitionEffect for Transition {
+    fn play(&self, sdl: &mut dyn Sdl, canvas_color: Color) -> FrameResult<()> {
+        Ok(Transition::play(self, sdl, canvas_color)?)
+    }
+
+    fn transition(&self) -> Option<Transition> {
+        Some(*self)
+    }
+}
+
+/// Picks the effect for each photo, resolving [Transition::Random]
+pub struct TransitionPicker {
+    transition: Transition,
+    last: Option<Transition>,
+    rand_gen_range: fn(Range<u32>) -> u32,
+}
 
 #[derive(Debug)]
 pub enum TransitionError {
@@ -24,25 +63,65 @@ pub enum TransitionError {
     Quit(QuitEvent),
 }
 
+impl TransitionPicker {
+    pub fn new(transition: Transition, rand_gen_range: fn(Range<u32>) -> u32) -> Self {
+        TransitionPicker {
+            transition,
+            last: None,
+            rand_gen_range,
+        }
+    }
+
+    /// Returns the effect for the next photo
+    pub fn pick_next(&mut self) -> Transition {
+        let transition = self.transition.pick(self.last, self.rand_gen_range);
+        self.last = Some(transition);
+        transition
+    }
+}
+
 impl Transition {
-    pub fn play(&self, sdl: &mut impl Sdl) -> Result<(), TransitionError> {
+    /// Resolves [Transition::Random] to one of [RANDOM_TRANSITIONS] other than `previous`. Other
+    /// transitions are returned as they are
+    fn pick(self, previous: Option<Transition>, rand_gen_range: fn(Range<u32>) -> u32) -> Self {
+        if self != Transition::Random {
+            return self;
+        }
+        let candidates: Vec<_> = RANDOM_TRANSITIONS
+            .into_iter()
+            .filter(|&transition| Some(transition) != previous)
+            .collect();
+        candidates[rand_gen_range(0..candidates.len() as u32) as usize]
+    }
+
+    /// Plays the effect. [Transition::FadeToBlack] fades through `fade_color`.
+    /// [Transition::Random] needs to be resolved with [TransitionPicker] first, otherwise the
+    /// first of [RANDOM_TRANSITIONS] is played
+    pub fn play(
+        &self,
+        sdl: &mut (impl Sdl + ?Sized),
+        fade_color: Color,
+    ) -> Result<(), TransitionError> {
         match self {
             Transition::Crossfade => {
                 self.crossfade(sdl)?;
             }
             Transition::FadeToBlack => {
-                self.fade_to_black(sdl, FadeToBlackPhase::Out)?;
-                self.fade_to_black(sdl, FadeToBlackPhase::In)?;
+                self.fade_to_black(sdl, FadeToBlackPhase::Out, fade_color)?;
+                self.fade_to_black(sdl, FadeToBlackPhase::In, fade_color)?;
             }
             Transition::None => {
                 sdl.copy_texture_to_canvas(TextureIndex::Next)?;
                 sdl.present_canvas();
             }
+            Transition::Random => {
+                RANDOM_TRANSITIONS[0].play(sdl, fade_color)?;
+            }
         }
         Ok(())
     }
 
-    fn crossfade(&self, sdl: &mut impl Sdl) -> Result<(), TransitionError> {
+    fn crossfade(&self, sdl: &mut (impl Sdl + ?Sized)) -> Result<(), TransitionError> {
         let mut delta;
         let mut alpha = TRANSITION_ALPHA_MIN;
         let mut last = Instant::now();
@@ -60,11 +139,12 @@ impl Transition {
         Ok(())
     }
 
-    /// Returns false if exit event occurred
+    /// Stops with [TransitionError::Quit] if exit event occurred
     fn fade_to_black(
         &self,
-        sdl: &mut impl Sdl,
+        sdl: &mut (impl Sdl + ?Sized),
         phase: FadeToBlackPhase,
+        Color { r, g, b, .. }: Color,
     ) -> Result<(), TransitionError> {
         let mut delta;
         let mut alpha = phase.init_alpha();
@@ -75,7 +155,7 @@ impl Transition {
             last = Instant::now();
             alpha += phase.step_alpha(delta);
             sdl.copy_texture_to_canvas(phase.texture_index())?;
-            sdl.fill_canvas(Color::RGBA(0, 0, 0, alpha.round() as u8))?;
+            sdl.fill_canvas(Color::RGBA(r, g, b, alpha.round() as u8))?;
             sdl.present_canvas();
         }
         Ok(())
@@ -151,6 +231,60 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn random_transition_is_not_repeated() {
+        let mut transitions = TransitionPicker::new(Transition::Random, |_| 0);
+
+        let picked = [(); 3].map(|_| transitions.pick_next());
+
+        assert_eq!(
+            picked,
+            [
+                Transition::Crossfade,
+                Transition::FadeToBlack,
+                Transition::Crossfade
+            ]
+        );
+    }
+
+    #[test]
+    fn fixed_transition_is_picked_as_it_is() {
+        let transition = Transition::FadeToBlack.pick(Some(Transition::FadeToBlack), |_| 0);
+
+        assert_eq!(transition, Transition::FadeToBlack);
+    }
+
+    #[test]
+    fn built_in_transition_plays_as_transition_effect() {
+        let mut sdl = MockSdl::default();
+        sdl.expect_copy_texture_to_canvas()
+            .withf(|index| index == &TextureIndex::Next)
+            .once()
+            .return_const(Ok(()));
+        sdl.expect_present_canvas().once().return_const(());
+        let effect: &dyn TransitionEffect = &Transition::None;
+
+        let result = effect.play(&mut sdl, Color::BLACK);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn unresolved_random_transition_plays_first_random_transition() {
+        let mut sdl = MockSdl::default();
+        sdl.expect_handle_quit_event().return_const(Ok(()));
+        sdl.expect_copy_texture_to_canvas().return_const(Ok(()));
+        sdl.expect_set_texture_alpha().return_const(());
+        sdl.expect_fill_canvas().never();
+        sdl.expect_present_canvas()
+            .returning(|| MockClock::advance(Duration::from_millis(10)));
+        let effect: &dyn TransitionEffect = &Transition::Random;
+
+        let result = effect.play(&mut sdl, Color::BLACK);
+
+        assert!(result.is_ok());
+    }
+
     #[test]
     fn fade_to_black_play_calls_canvas_methods_in_sequence() {
         let mut sdl = MockSdl::default();
@@ -185,7 +319,7 @@ mod tests {
             }
         }
 
-        let result = Transition::FadeToBlack.play(&mut sdl);
+        let result = Transition::FadeToBlack.play(&mut sdl, Color::BLACK);
 
         assert!(result.is_ok());
         sdl.checkpoint();
@@ -227,12 +361,56 @@ mod tests {
                 });
         }
 
-        let result = Transition::Crossfade.play(&mut sdl);
+        let result = Transition::Crossfade.play(&mut sdl, Color::BLACK);
 
         assert!(result.is_ok());
         sdl.checkpoint();
     }
 
+    #[test]
+    fn fade_to_black_fades_through_given_color() {
+        let mut sdl = MockSdl::default();
+        sdl.expect_handle_quit_event().return_const(Ok(()));
+        sdl.expect_copy_texture_to_canvas().return_const(Ok(()));
+        sdl.expect_fill_canvas()
+            .withf(|color| (color.r, color.g, color.b) == (255, 255, 255))
+            .return_const(Ok(()));
+        sdl.expect_present_canvas()
+            .returning(|| MockClock::advance(Duration::from_millis(10)));
+
+        let result = Transition::FadeToBlack.play(&mut sdl, Color::WHITE);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn when_quit_event_occurs_during_transition_then_play_stops_early() {
+        for transition in [Transition::Crossfade, Transition::FadeToBlack] {
+            let mut sdl = MockSdl::default();
+            const FRAMES_BEFORE_QUIT: usize = 3;
+            let mut quit_checks = 0;
+            sdl.expect_handle_quit_event().returning(move || {
+                quit_checks += 1;
+                if quit_checks > FRAMES_BEFORE_QUIT {
+                    Err(QuitEvent)
+                } else {
+                    Ok(())
+                }
+            });
+            sdl.expect_copy_texture_to_canvas().return_const(Ok(()));
+            sdl.expect_set_texture_alpha().return_const(());
+            sdl.expect_fill_canvas().return_const(Ok(()));
+            sdl.expect_present_canvas()
+                .times(FRAMES_BEFORE_QUIT)
+                .returning(|| MockClock::advance(Duration::from_millis(10)));
+
+            let result = transition.play(&mut sdl, Color::BLACK);
+
+            assert!(matches!(result, Err(TransitionError::Quit(QuitEvent))));
+            sdl.checkpoint();
+        }
+    }
+
     #[test]
     fn fade_to_black_play_takes_one_second_and_is_fps_independent() {
         test_case(30_f64);
@@ -248,7 +426,9 @@ mod tests {
                 .returning(move || MockClock::advance(frame_duration));
             reset_clock();
 
-            Transition::FadeToBlack.play(&mut sdl).unwrap();
+            Transition::FadeToBlack
+                .play(&mut sdl, Color::BLACK)
+                .unwrap();
 
             let fade_duration = MockClock::time();
             assert_eq!(fade_duration.as_secs(), 1);
@@ -270,7 +450,7 @@ mod tests {
                 .returning(move || MockClock::advance(frame_duration));
             reset_clock();
 
-            Transition::Crossfade.play(&mut sdl).unwrap();
+            Transition::Crossfade.play(&mut sdl, Color::BLACK).unwrap();
 
             let fade_duration = MockClock::time();
             assert_eq!(fade_duration.as_secs(), 1);
@@ -311,7 +491,9 @@ mod tests {
         sdl.expect_present_canvas()
             .returning(move || MockClock::advance(frame_duration));
 
-        Transition::FadeToBlack.play(&mut sdl).unwrap();
+        Transition::FadeToBlack
+            .play(&mut sdl, Color::BLACK)
+            .unwrap();
 
         sdl.checkpoint();
     }
@@ -351,7 +533,7 @@ mod tests {
         sdl.expect_present_canvas()
             .returning(move || MockClock::advance(frame_duration));
 
-        Transition::Crossfade.play(&mut sdl).unwrap();
+        Transition::Crossfade.play(&mut sdl, Color::BLACK).unwrap();
 
         sdl.checkpoint();
     }
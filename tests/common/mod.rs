@@ -0,0 +1,184 @@
+//! Minimal in-process FTP server serving a fixture directory
+
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    net::{Ipv4Addr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    thread,
+};
+
+/// Set to skip tests that need to bind loopback TCP ports, e.g. in sandboxes without networking
+const SKIP_NETWORK_TESTS_VARIABLE: &str = "SKIP_NETWORK_TESTS";
+
+/// Checks whether tests using [FtpServer] should return early without running
+pub fn network_tests_skipped() -> bool {
+    let skipped = env::var_os(SKIP_NETWORK_TESTS_VARIABLE).is_some();
+    if skipped {
+        eprintln!("{SKIP_NETWORK_TESTS_VARIABLE} is set, skipping");
+    }
+    skipped
+}
+
+/// Serves files from a directory to any number of sessions, until the test process exits.
+///
+/// Only the commands sent by `syno_photo_frame::ftp` are supported, with passive mode data
+/// connections on the loopback interface. Like real servers, NLST lists subfolders along with
+/// files
+pub struct FtpServer {
+    port: u16,
+}
+
+impl FtpServer {
+    /// Starts a server advertising MLSD support
+    pub fn start(root: impl Into<PathBuf>) -> FtpServer {
+        FtpServer::spawn(root.into(), true)
+    }
+
+    /// Starts a server without MLSD support, which only lists folders with NLST
+    pub fn start_without_mlsd(root: impl Into<PathBuf>) -> FtpServer {
+        FtpServer::spawn(root.into(), false)
+    }
+
+    fn spawn(root: PathBuf, supports_mlsd: bool) -> FtpServer {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).expect("bind control port");
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            for control_stream in listener.incoming().flatten() {
+                let root = root.clone();
+                thread::spawn(move || {
+                    /* Errors mean the client disconnected, which ends the session */
+                    let _ = serve_session(control_stream, &root, supports_mlsd);
+                });
+            }
+        });
+        FtpServer { port }
+    }
+
+    /// URL of the fixture directory root
+    pub fn url(&self) -> String {
+        format!("ftp://127.0.0.1:{}/", self.port)
+    }
+}
+
+fn serve_session(
+    control_stream: TcpStream,
+    root: &Path,
+    supports_mlsd: bool,
+) -> std::io::Result<()> {
+    let mut reader = BufReader::new(control_stream.try_clone()?);
+    let mut writer = control_stream;
+    let mut working_dir = root.to_path_buf();
+    let mut data_listener: Option<TcpListener> = None;
+    writer.write_all(b"220 Fixture FTP server ready\r\n")?;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let line = line.trim_end();
+        let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+        let reply = match command.to_ascii_uppercase().as_str() {
+            "USER" => "331 Password required".to_string(),
+            "PASS" => "230 Logged in".to_string(),
+            "FEAT" if supports_mlsd => {
+                "211-Features:\r\n MLST type*;size*;\r\n UTF8\r\n211 End".to_string()
+            }
+            "FEAT" => "211-Features:\r\n UTF8\r\n211 End".to_string(),
+            "OPTS" => "200 OK".to_string(),
+            "TYPE" => "200 Type set".to_string(),
+            "SIZE" => match resolve(root, &working_dir, argument).and_then(|p| p.metadata().ok()) {
+                Some(metadata) if metadata.is_file() => format!("213 {}", metadata.len()),
+                _ => "550 No such file".to_string(),
+            },
+            "CWD" => match resolve(root, &working_dir, argument) {
+                Some(path) if path.is_dir() => {
+                    working_dir = path;
+                    "250 Directory changed".to_string()
+                }
+                _ => "550 No such directory".to_string(),
+            },
+            "PASV" => {
+                let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+                let port = listener.local_addr()?.port();
+                data_listener = Some(listener);
+                format!(
+                    "227 Entering Passive Mode (127,0,0,1,{},{})",
+                    port >> 8,
+                    port & 0xff
+                )
+            }
+            "MLSD" if !supports_mlsd => "502 Command not implemented".to_string(),
+            "NLST" | "MLSD" | "RETR" => {
+                let data = match command.to_ascii_uppercase().as_str() {
+                    "NLST" => list(&working_dir, |name, _| format!("{name}\r\n")),
+                    "MLSD" => list(&working_dir, |name, is_file| {
+                        let kind = if is_file { "file" } else { "dir" };
+                        format!("type={kind}; {name}\r\n")
+                    }),
+                    _ => resolve(root, &working_dir, argument)
+                        .filter(|path| path.is_file())
+                        .and_then(|path| fs::read(path).ok()),
+                };
+                match (data, data_listener.take()) {
+                    (Some(data), Some(listener)) => {
+                        writer.write_all(b"150 Opening data connection\r\n")?;
+                        let (mut data_stream, _) = listener.accept()?;
+                        data_stream.write_all(&data)?;
+                        drop(data_stream);
+                        "226 Transfer complete".to_string()
+                    }
+                    (None, _) => "550 No such file".to_string(),
+                    (_, None) => "425 Use PASV first".to_string(),
+                }
+            }
+            "QUIT" => {
+                writer.write_all(b"221 Bye\r\n")?;
+                return Ok(());
+            }
+            _ => "502 Command not implemented".to_string(),
+        };
+        writer.write_all(format!("{reply}\r\n").as_bytes())?;
+    }
+}
+
+/// Resolves a path sent by the client, refusing to leave the root
+fn resolve(root: &Path, working_dir: &Path, path: &str) -> Option<PathBuf> {
+    let mut resolved = if path.starts_with('/') {
+        root.to_path_buf()
+    } else {
+        working_dir.to_path_buf()
+    };
+    for component in path.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        if component == ".." {
+            if resolved == root {
+                return None;
+            }
+            resolved.pop();
+        } else {
+            resolved.push(component);
+        }
+    }
+    Some(resolved)
+}
+
+/// Lists files and subfolders of a directory, formatting each with `line` given the entry name
+/// and whether it is a file
+fn list(dir: &Path, line: impl Fn(&str, bool) -> String) -> Option<Vec<u8>> {
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            (name, entry.path().is_file())
+        })
+        .collect();
+    entries.sort();
+    Some(
+        entries
+            .iter()
+            .map(|(name, is_file)| line(name, *is_file))
+            .collect::<String>()
+            .into_bytes(),
+    )
+}
@@ -0,0 +1,182 @@
+//! Drives [Slideshow] end to end against a local FTP server serving `tests/fixtures/album`.
+//!
+//! The tests need to bind loopback TCP ports. Where that is not possible, set
+//! `SKIP_NETWORK_TESTS` to skip them, e.g. `SKIP_NETWORK_TESTS=1 cargo test`
+
+mod common;
+
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use syno_photo_frame::{cli::Order, ftp, slideshow::Slideshow, Random};
+
+use common::FtpServer;
+
+const FIXTURE_ALBUM: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/album");
+
+const NOT_RANDOM: Random = (|range| range.start, |_| ());
+
+fn new_client(server: &FtpServer, path: &str) -> ftp::Client {
+    let url = format!("{}{path}", server.url()).parse().unwrap();
+    ftp::Client::new(&url, &None, &None)
+}
+
+#[test]
+fn ftp_slideshow_fetches_all_photos_in_order_with_sidecar_durations() {
+    if common::network_tests_skipped() {
+        return;
+    }
+    let server = FtpServer::start(FIXTURE_ALBUM);
+    let mut client = new_client(&server, "");
+    let mut slideshow = Slideshow::new().with_folders(vec![(String::new(), Order::ByName)]);
+
+    let photo_files: Vec<_> = (0..3)
+        .map(|_| slideshow.get_next_photo(&mut client, NOT_RANDOM).unwrap())
+        .collect();
+
+    /* notes.txt is excluded by .frameignore and the .duration sidecar is not a photo */
+    assert!(slideshow.slideshow_ended());
+    let names: Vec<_> = photo_files.iter().map(|file| file.name.as_str()).collect();
+    assert_eq!(names, ["IMG_1.jpg", "IMG_2.jpg", "IMG_10.jpg"]);
+    let bytes: Vec<_> = photo_files.iter().map(|file| file.bytes.clone()).collect();
+    assert_eq!(
+        bytes,
+        [
+            Bytes::from_static(b"IMG_1"),
+            Bytes::from_static(b"IMG_2"),
+            Bytes::from_static(b"IMG_10")
+        ]
+    );
+    let durations: Vec<_> = photo_files
+        .iter()
+        .map(|file| file.display_duration)
+        .collect();
+    assert_eq!(durations, [None, Some(Duration::from_secs(15)), None]);
+}
+
+#[test]
+fn ftp_slideshow_skips_subfolders_listed_by_nlst() {
+    if common::network_tests_skipped() {
+        return;
+    }
+    let server = FtpServer::start_without_mlsd(FIXTURE_ALBUM);
+    let mut client = new_client(&server, "");
+    let mut slideshow = Slideshow::new().with_folders(vec![(String::new(), Order::ByName)]);
+
+    let names: Vec<_> = (0..4)
+        .map(|_| {
+            slideshow
+                .get_next_photo(&mut client, NOT_RANDOM)
+                .unwrap()
+                .name
+        })
+        .collect();
+
+    assert_eq!(names, ["IMG_1.jpg", "IMG_2.jpg", "IMG_10.jpg", "IMG_1.jpg"]);
+}
+
+#[test]
+fn ftp_slideshow_restarts_after_last_photo() {
+    if common::network_tests_skipped() {
+        return;
+    }
+    let server = FtpServer::start(FIXTURE_ALBUM);
+    let mut client = new_client(&server, "2024");
+    let mut slideshow = Slideshow::new();
+
+    let names: Vec<_> = (0..3)
+        .map(|_| {
+            slideshow
+                .get_next_photo(&mut client, NOT_RANDOM)
+                .unwrap()
+                .name
+        })
+        .collect();
+
+    assert_eq!(names, ["a.jpg", "b.jpg", "a.jpg"]);
+}
+
+#[test]
+fn ftp_slideshow_opens_album_path_with_non_ascii_characters_and_spaces() {
+    if common::network_tests_skipped() {
+        return;
+    }
+    let server = FtpServer::start(FIXTURE_ALBUM);
+    let mut client = new_client(&server, "Été 2024");
+    let mut slideshow = Slideshow::new();
+
+    let photo_file = slideshow.get_next_photo(&mut client, NOT_RANDOM).unwrap();
+
+    assert_eq!(photo_file.name, "c.jpg");
+    assert_eq!(photo_file.bytes, Bytes::from_static(b"c"));
+}
+
+#[test]
+fn ftp_slideshow_interleaves_folders() {
+    if common::network_tests_skipped() {
+        return;
+    }
+    let server = FtpServer::start(FIXTURE_ALBUM);
+    let mut client = new_client(&server, "");
+    let mut slideshow = Slideshow::new().with_folders(vec![
+        (String::new(), Order::ByName),
+        ("2024".to_string(), Order::ByName),
+    ]);
+
+    let names: Vec<_> = (0..5)
+        .map(|_| {
+            slideshow
+                .get_next_photo(&mut client, NOT_RANDOM)
+                .unwrap()
+                .name
+        })
+        .collect();
+
+    assert_eq!(
+        names,
+        [
+            "IMG_1.jpg",
+            "2024/a.jpg",
+            "IMG_2.jpg",
+            "2024/b.jpg",
+            "IMG_10.jpg"
+        ]
+    );
+}
+
+#[test]
+fn ftp_slideshow_displays_folder_that_is_a_file() {
+    if common::network_tests_skipped() {
+        return;
+    }
+    let server = FtpServer::start(FIXTURE_ALBUM);
+    let mut client = new_client(&server, "");
+    let mut slideshow =
+        Slideshow::new().with_folders(vec![("2024/b.jpg".to_string(), Order::ByName)]);
+
+    let names: Vec<_> = (0..2)
+        .map(|_| {
+            slideshow
+                .get_next_photo(&mut client, NOT_RANDOM)
+                .unwrap()
+                .name
+        })
+        .collect();
+
+    assert_eq!(names, ["2024/b.jpg", "2024/b.jpg"]);
+}
+
+#[test]
+fn ftp_slideshow_returns_error_when_album_folder_does_not_exist() {
+    if common::network_tests_skipped() {
+        return;
+    }
+    let server = FtpServer::start(FIXTURE_ALBUM);
+    let mut client = new_client(&server, "missing");
+    let mut slideshow = Slideshow::new();
+
+    let result = slideshow.get_next_photo(&mut client, NOT_RANDOM);
+
+    assert!(result.is_err());
+}